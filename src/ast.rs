@@ -0,0 +1,879 @@
+//! The syntax tree produced by the parser.
+//!
+//! Every node keeps the tokens it was built from, including punctuation such as parentheses, commas
+//! and semicolons. The formatter needs all of them to decide what to print, and keeping them around
+//! also means every node knows its own position in the source.
+//!
+//! The tree is lossless: whitespace, newlines and comments are attached to the tokens around them
+//! (see [`TokenReference`]), so printing a [`Chunk`] gives back the exact source it was parsed from.
+
+// Nodes are built once and then only walked, so evening out variant sizes by boxing every large
+// variant isn't worth the extra indirection.
+#![allow(clippy::large_enum_variant)]
+
+use std::fmt;
+use std::ops::Deref;
+
+use derive_more::Constructor;
+
+use crate::lexer::Token;
+
+/// A significant token together with the trivia (whitespace, newlines and comments) around it.
+///
+/// Trailing trivia is everything after the token up to and including the end of its line. All other
+/// trivia is the leading trivia of the next token. Trivia at the very end of a file is the leading
+/// trivia of the `Eof` token.
+#[derive(Constructor, Debug, PartialEq)]
+pub struct TokenReference<'src> {
+    pub leading_trivia: Vec<Token<'src>>,
+    pub token: Token<'src>,
+    pub trailing_trivia: Vec<Token<'src>>,
+}
+
+impl<'src> Deref for TokenReference<'src> {
+    type Target = Token<'src>;
+
+    fn deref(&self) -> &Token<'src> {
+        &self.token
+    }
+}
+
+impl fmt::Display for TokenReference<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trivia in &self.leading_trivia {
+            write!(f, "{}", trivia.kind)?;
+        }
+        write!(f, "{}", self.token.kind)?;
+        for trivia in &self.trailing_trivia {
+            write!(f, "{}", trivia.kind)?;
+        }
+        Ok(())
+    }
+}
+
+/// A list of items, each optionally followed by a separator token.
+///
+/// Squirrel is lenient about separators in a lot of places (e.g. commas in table and array literals
+/// are optional), so the separator is kept per item rather than assumed.
+pub type Punctuated<'src, T> = Vec<(T, Option<TokenReference<'src>>)>;
+
+/// The root of a Squirrel script.
+#[derive(Debug)]
+pub struct Chunk<'src> {
+    pub stmts: Vec<Stmt<'src>>,
+    pub eof: TokenReference<'src>,
+}
+
+#[derive(Debug)]
+pub struct Stmt<'src> {
+    pub kind: StmtKind<'src>,
+    pub semicolon: Option<TokenReference<'src>>,
+}
+
+#[derive(Debug)]
+pub enum StmtKind<'src> {
+    /// A lone `;`, which is stored as the statement's semicolon.
+    Empty,
+    Expr(Expr<'src>),
+    Block(Block<'src>),
+    If {
+        if_token: TokenReference<'src>,
+        paren_open: TokenReference<'src>,
+        cond: Expr<'src>,
+        paren_close: TokenReference<'src>,
+        body: Box<Stmt<'src>>,
+        else_branch: Option<(TokenReference<'src>, Box<Stmt<'src>>)>,
+    },
+    While {
+        while_token: TokenReference<'src>,
+        paren_open: TokenReference<'src>,
+        cond: Expr<'src>,
+        paren_close: TokenReference<'src>,
+        body: Box<Stmt<'src>>,
+    },
+    DoWhile {
+        do_token: TokenReference<'src>,
+        body: Box<Stmt<'src>>,
+        while_token: TokenReference<'src>,
+        paren_open: TokenReference<'src>,
+        cond: Expr<'src>,
+        paren_close: TokenReference<'src>,
+    },
+    For {
+        for_token: TokenReference<'src>,
+        paren_open: TokenReference<'src>,
+        init: Option<ForInit<'src>>,
+        first_semicolon: TokenReference<'src>,
+        cond: Option<Expr<'src>>,
+        second_semicolon: TokenReference<'src>,
+        step: Option<Expr<'src>>,
+        paren_close: TokenReference<'src>,
+        body: Box<Stmt<'src>>,
+    },
+    Foreach {
+        foreach_token: TokenReference<'src>,
+        paren_open: TokenReference<'src>,
+        /// The key variable and the comma after it, i.e. `k,` in `foreach (k, v in t)`.
+        key: Option<(TokenReference<'src>, TokenReference<'src>)>,
+        value: TokenReference<'src>,
+        in_token: TokenReference<'src>,
+        iterable: Expr<'src>,
+        paren_close: TokenReference<'src>,
+        body: Box<Stmt<'src>>,
+    },
+    Switch {
+        switch_token: TokenReference<'src>,
+        paren_open: TokenReference<'src>,
+        subject: Expr<'src>,
+        paren_close: TokenReference<'src>,
+        brace_open: TokenReference<'src>,
+        cases: Vec<SwitchCase<'src>>,
+        brace_close: TokenReference<'src>,
+    },
+    Local(Local<'src>),
+    LocalFunction {
+        local_token: TokenReference<'src>,
+        function_token: TokenReference<'src>,
+        name: TokenReference<'src>,
+        body: FunctionBody<'src>,
+    },
+    Function {
+        function_token: TokenReference<'src>,
+        name: FunctionName<'src>,
+        body: FunctionBody<'src>,
+    },
+    Class {
+        class_token: TokenReference<'src>,
+        name: Expr<'src>,
+        body: Box<ClassBody<'src>>,
+    },
+    Enum {
+        enum_token: TokenReference<'src>,
+        name: TokenReference<'src>,
+        brace_open: TokenReference<'src>,
+        members: Punctuated<'src, EnumMember<'src>>,
+        brace_close: TokenReference<'src>,
+    },
+    Const {
+        const_token: TokenReference<'src>,
+        name: TokenReference<'src>,
+        eq: TokenReference<'src>,
+        value: Expr<'src>,
+    },
+    /// `return` or `yield`, with an optional value.
+    Return {
+        keyword: TokenReference<'src>,
+        value: Option<Expr<'src>>,
+    },
+    Break(TokenReference<'src>),
+    Continue(TokenReference<'src>),
+    Throw {
+        throw_token: TokenReference<'src>,
+        value: Expr<'src>,
+    },
+    Try {
+        try_token: TokenReference<'src>,
+        body: Box<Stmt<'src>>,
+        catch_token: TokenReference<'src>,
+        paren_open: TokenReference<'src>,
+        binding: TokenReference<'src>,
+        paren_close: TokenReference<'src>,
+        catch_body: Box<Stmt<'src>>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Block<'src> {
+    pub brace_open: TokenReference<'src>,
+    pub stmts: Vec<Stmt<'src>>,
+    pub brace_close: TokenReference<'src>,
+}
+
+#[derive(Debug)]
+pub enum ForInit<'src> {
+    Local(Local<'src>),
+    Expr(Expr<'src>),
+}
+
+#[derive(Debug)]
+pub struct Local<'src> {
+    pub local_token: TokenReference<'src>,
+    pub vars: Punctuated<'src, LocalVar<'src>>,
+}
+
+#[derive(Debug)]
+pub struct LocalVar<'src> {
+    pub name: TokenReference<'src>,
+    /// The `=` and the initial value.
+    pub init: Option<(TokenReference<'src>, Expr<'src>)>,
+}
+
+#[derive(Debug)]
+pub struct SwitchCase<'src> {
+    pub label: CaseLabel<'src>,
+    pub colon: TokenReference<'src>,
+    pub stmts: Vec<Stmt<'src>>,
+}
+
+#[derive(Debug)]
+pub enum CaseLabel<'src> {
+    Case(TokenReference<'src>, Expr<'src>),
+    Default(TokenReference<'src>),
+}
+
+#[derive(Debug)]
+pub struct EnumMember<'src> {
+    pub name: TokenReference<'src>,
+    /// The `=` and the value.
+    pub value: Option<(TokenReference<'src>, Expr<'src>)>,
+}
+
+/// The name of a function statement, e.g. `foo` or `foo::bar::baz`.
+#[derive(Debug)]
+pub struct FunctionName<'src> {
+    pub first: TokenReference<'src>,
+    /// Every following `::` and identifier.
+    pub rest: Vec<(TokenReference<'src>, TokenReference<'src>)>,
+}
+
+/// Parameters and body, shared by every kind of function.
+#[derive(Debug)]
+pub struct FunctionBody<'src> {
+    pub params: Params<'src>,
+    pub body: Box<Stmt<'src>>,
+}
+
+#[derive(Debug)]
+pub struct Params<'src> {
+    pub paren_open: TokenReference<'src>,
+    pub params: Punctuated<'src, Param<'src>>,
+    pub paren_close: TokenReference<'src>,
+}
+
+#[derive(Debug)]
+pub enum Param<'src> {
+    Named {
+        name: TokenReference<'src>,
+        /// The `=` and the default value.
+        default: Option<(TokenReference<'src>, Expr<'src>)>,
+    },
+    Varargs(TokenReference<'src>),
+}
+
+/// Everything after the name of a class: the base class, attributes and members.
+#[derive(Debug)]
+pub struct ClassBody<'src> {
+    /// The `extends` keyword and the base class.
+    pub extends: Option<(TokenReference<'src>, Box<Expr<'src>>)>,
+    pub attributes: Option<Attributes<'src>>,
+    pub brace_open: TokenReference<'src>,
+    pub members: Vec<ClassMember<'src>>,
+    pub brace_close: TokenReference<'src>,
+}
+
+#[derive(Debug)]
+pub struct ClassMember<'src> {
+    pub attributes: Option<Attributes<'src>>,
+    pub static_token: Option<TokenReference<'src>>,
+    pub entry: TableEntry<'src>,
+    /// Class members are optionally separated by `;`.
+    pub separator: Option<TokenReference<'src>>,
+}
+
+/// Class attributes, i.e. `</ key = value />`.
+#[derive(Debug)]
+pub struct Attributes<'src> {
+    pub open: TokenReference<'src>,
+    pub entries: Punctuated<'src, TableEntry<'src>>,
+    pub close: TokenReference<'src>,
+}
+
+/// A slot in a table literal, a class body or an attribute list.
+#[derive(Debug)]
+pub enum TableEntry<'src> {
+    /// `key = value`
+    Field {
+        name: TokenReference<'src>,
+        eq: TokenReference<'src>,
+        value: Expr<'src>,
+    },
+    /// `[key] = value`
+    Computed {
+        square_open: TokenReference<'src>,
+        key: Expr<'src>,
+        square_close: TokenReference<'src>,
+        eq: TokenReference<'src>,
+        value: Expr<'src>,
+    },
+    /// `"key": value`
+    Json {
+        key: TokenReference<'src>,
+        colon: TokenReference<'src>,
+        value: Expr<'src>,
+    },
+    /// `function name(...) { ... }`
+    Function {
+        function_token: TokenReference<'src>,
+        name: TokenReference<'src>,
+        body: FunctionBody<'src>,
+    },
+    /// `constructor(...) { ... }`
+    Constructor {
+        constructor_token: TokenReference<'src>,
+        body: FunctionBody<'src>,
+    },
+}
+
+#[derive(Debug)]
+pub enum Expr<'src> {
+    /// A single token expression: identifiers, literals, `null`, `true`, `false`, `this`, `base`,
+    /// `constructor`, `rawcall`, `__FILE__` and `__LINE__`, or `parent`, `vargc` and `vargv` in
+    /// Squirrel 2.
+    Atom(TokenReference<'src>),
+    /// `::name`
+    Root {
+        scope: TokenReference<'src>,
+        name: TokenReference<'src>,
+    },
+    Paren {
+        paren_open: TokenReference<'src>,
+        expr: Box<Expr<'src>>,
+        paren_close: TokenReference<'src>,
+    },
+    Array {
+        square_open: TokenReference<'src>,
+        items: Punctuated<'src, Expr<'src>>,
+        square_close: TokenReference<'src>,
+    },
+    Table {
+        brace_open: TokenReference<'src>,
+        entries: Punctuated<'src, TableEntry<'src>>,
+        brace_close: TokenReference<'src>,
+    },
+    Function {
+        function_token: TokenReference<'src>,
+        body: FunctionBody<'src>,
+    },
+    Lambda {
+        at: TokenReference<'src>,
+        params: Params<'src>,
+        body: Box<Expr<'src>>,
+    },
+    Class {
+        class_token: TokenReference<'src>,
+        body: Box<ClassBody<'src>>,
+    },
+    /// `delegate parent : expr`, which sets the delegate of a table in Squirrel 2.
+    Delegate {
+        delegate_token: TokenReference<'src>,
+        parent: Box<Expr<'src>>,
+        colon: TokenReference<'src>,
+        expr: Box<Expr<'src>>,
+    },
+    /// A prefix operation: `-`, `!`, `~`, `++`, `--`, `typeof`, `clone`, `resume` or `delete`.
+    Unary {
+        op: TokenReference<'src>,
+        expr: Box<Expr<'src>>,
+    },
+    /// A postfix `++` or `--`.
+    Postfix {
+        expr: Box<Expr<'src>>,
+        op: TokenReference<'src>,
+    },
+    /// Any binary operation, including assignments and the comma operator.
+    Binary {
+        lhs: Box<Expr<'src>>,
+        op: TokenReference<'src>,
+        rhs: Box<Expr<'src>>,
+    },
+    Ternary {
+        cond: Box<Expr<'src>>,
+        question: TokenReference<'src>,
+        then_branch: Box<Expr<'src>>,
+        colon: TokenReference<'src>,
+        else_branch: Box<Expr<'src>>,
+    },
+    Field {
+        expr: Box<Expr<'src>>,
+        dot: TokenReference<'src>,
+        name: TokenReference<'src>,
+    },
+    Index {
+        expr: Box<Expr<'src>>,
+        square_open: TokenReference<'src>,
+        index: Box<Expr<'src>>,
+        square_close: TokenReference<'src>,
+    },
+    Call {
+        expr: Box<Expr<'src>>,
+        paren_open: TokenReference<'src>,
+        args: Punctuated<'src, Expr<'src>>,
+        paren_close: TokenReference<'src>,
+    },
+}
+
+/// Implemented by every node of the syntax tree.
+pub trait Node {
+    /// Pushes every token of the node to `tokens`, in source order.
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>);
+
+    /// The first token of the node, if it has any.
+    fn first_token(&self) -> Option<&TokenReference<'_>> {
+        let mut tokens = Vec::new();
+        self.tokens(&mut tokens);
+        tokens.first().copied()
+    }
+}
+
+// Calls `Node::tokens` on each of the given nodes, in order.
+macro_rules! push_tokens {
+    ($tokens: expr; $($node: expr),+ $(,)?) => {{
+        $($node.tokens($tokens);)+
+    }};
+}
+
+impl Node for TokenReference<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        tokens.push(self);
+    }
+}
+
+impl<T: Node> Node for Box<T> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        (**self).tokens(tokens);
+    }
+}
+
+impl<T: Node> Node for Option<T> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        if let Some(node) = self {
+            node.tokens(tokens);
+        }
+    }
+}
+
+impl<T: Node> Node for Vec<T> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        for node in self {
+            node.tokens(tokens);
+        }
+    }
+}
+
+impl<A: Node, B: Node> Node for (A, B) {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.0, self.1);
+    }
+}
+
+impl Node for Chunk<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.stmts, self.eof);
+    }
+}
+
+impl fmt::Display for Chunk<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tokens = Vec::new();
+        self.tokens(&mut tokens);
+        for token in tokens {
+            write!(f, "{token}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Node for Stmt<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.kind, self.semicolon);
+    }
+}
+
+impl Node for StmtKind<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        match self {
+            StmtKind::Empty => {}
+            StmtKind::Expr(expr) => expr.tokens(tokens),
+            StmtKind::Block(block) => block.tokens(tokens),
+            StmtKind::If {
+                if_token,
+                paren_open,
+                cond,
+                paren_close,
+                body,
+                else_branch,
+            } => push_tokens!(tokens; if_token, paren_open, cond, paren_close, body, else_branch),
+            StmtKind::While {
+                while_token,
+                paren_open,
+                cond,
+                paren_close,
+                body,
+            } => push_tokens!(tokens; while_token, paren_open, cond, paren_close, body),
+            StmtKind::DoWhile {
+                do_token,
+                body,
+                while_token,
+                paren_open,
+                cond,
+                paren_close,
+            } => push_tokens!(tokens; do_token, body, while_token, paren_open, cond, paren_close),
+            StmtKind::For {
+                for_token,
+                paren_open,
+                init,
+                first_semicolon,
+                cond,
+                second_semicolon,
+                step,
+                paren_close,
+                body,
+            } => push_tokens!(
+                tokens;
+                for_token,
+                paren_open,
+                init,
+                first_semicolon,
+                cond,
+                second_semicolon,
+                step,
+                paren_close,
+                body,
+            ),
+            StmtKind::Foreach {
+                foreach_token,
+                paren_open,
+                key,
+                value,
+                in_token,
+                iterable,
+                paren_close,
+                body,
+            } => push_tokens!(
+                tokens;
+                foreach_token,
+                paren_open,
+                key,
+                value,
+                in_token,
+                iterable,
+                paren_close,
+                body,
+            ),
+            StmtKind::Switch {
+                switch_token,
+                paren_open,
+                subject,
+                paren_close,
+                brace_open,
+                cases,
+                brace_close,
+            } => push_tokens!(
+                tokens;
+                switch_token,
+                paren_open,
+                subject,
+                paren_close,
+                brace_open,
+                cases,
+                brace_close,
+            ),
+            StmtKind::Local(local) => local.tokens(tokens),
+            StmtKind::LocalFunction {
+                local_token,
+                function_token,
+                name,
+                body,
+            } => push_tokens!(tokens; local_token, function_token, name, body),
+            StmtKind::Function {
+                function_token,
+                name,
+                body,
+            } => push_tokens!(tokens; function_token, name, body),
+            StmtKind::Class {
+                class_token,
+                name,
+                body,
+            } => push_tokens!(tokens; class_token, name, body),
+            StmtKind::Enum {
+                enum_token,
+                name,
+                brace_open,
+                members,
+                brace_close,
+            } => push_tokens!(tokens; enum_token, name, brace_open, members, brace_close),
+            StmtKind::Const {
+                const_token,
+                name,
+                eq,
+                value,
+            } => push_tokens!(tokens; const_token, name, eq, value),
+            StmtKind::Return { keyword, value } => push_tokens!(tokens; keyword, value),
+            StmtKind::Break(token) | StmtKind::Continue(token) => token.tokens(tokens),
+            StmtKind::Throw { throw_token, value } => push_tokens!(tokens; throw_token, value),
+            StmtKind::Try {
+                try_token,
+                body,
+                catch_token,
+                paren_open,
+                binding,
+                paren_close,
+                catch_body,
+            } => push_tokens!(
+                tokens;
+                try_token,
+                body,
+                catch_token,
+                paren_open,
+                binding,
+                paren_close,
+                catch_body,
+            ),
+        }
+    }
+}
+
+impl Node for Block<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.brace_open, self.stmts, self.brace_close);
+    }
+}
+
+impl Node for ForInit<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        match self {
+            ForInit::Local(local) => local.tokens(tokens),
+            ForInit::Expr(expr) => expr.tokens(tokens),
+        }
+    }
+}
+
+impl Node for Local<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.local_token, self.vars);
+    }
+}
+
+impl Node for LocalVar<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.name, self.init);
+    }
+}
+
+impl Node for SwitchCase<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.label, self.colon, self.stmts);
+    }
+}
+
+impl Node for CaseLabel<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        match self {
+            CaseLabel::Case(case_token, expr) => push_tokens!(tokens; case_token, expr),
+            CaseLabel::Default(default_token) => default_token.tokens(tokens),
+        }
+    }
+}
+
+impl Node for EnumMember<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.name, self.value);
+    }
+}
+
+impl Node for FunctionName<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.first, self.rest);
+    }
+}
+
+impl Node for FunctionBody<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.params, self.body);
+    }
+}
+
+impl Node for Params<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.paren_open, self.params, self.paren_close);
+    }
+}
+
+impl Node for Param<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        match self {
+            Param::Named { name, default } => push_tokens!(tokens; name, default),
+            Param::Varargs(ellipsis) => ellipsis.tokens(tokens),
+        }
+    }
+}
+
+impl Node for ClassBody<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(
+            tokens;
+            self.extends,
+            self.attributes,
+            self.brace_open,
+            self.members,
+            self.brace_close,
+        );
+    }
+}
+
+impl Node for ClassMember<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.attributes, self.static_token, self.entry, self.separator);
+    }
+}
+
+impl Node for Attributes<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        push_tokens!(tokens; self.open, self.entries, self.close);
+    }
+}
+
+impl Node for TableEntry<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        match self {
+            TableEntry::Field { name, eq, value } => push_tokens!(tokens; name, eq, value),
+            TableEntry::Computed {
+                square_open,
+                key,
+                square_close,
+                eq,
+                value,
+            } => push_tokens!(tokens; square_open, key, square_close, eq, value),
+            TableEntry::Json { key, colon, value } => push_tokens!(tokens; key, colon, value),
+            TableEntry::Function {
+                function_token,
+                name,
+                body,
+            } => push_tokens!(tokens; function_token, name, body),
+            TableEntry::Constructor {
+                constructor_token,
+                body,
+            } => push_tokens!(tokens; constructor_token, body),
+        }
+    }
+}
+
+impl Node for Expr<'_> {
+    fn tokens<'a>(&'a self, tokens: &mut Vec<&'a TokenReference<'a>>) {
+        match self {
+            Expr::Atom(token) => token.tokens(tokens),
+            Expr::Root { scope, name } => push_tokens!(tokens; scope, name),
+            Expr::Paren {
+                paren_open,
+                expr,
+                paren_close,
+            } => push_tokens!(tokens; paren_open, expr, paren_close),
+            Expr::Array {
+                square_open,
+                items,
+                square_close,
+            } => push_tokens!(tokens; square_open, items, square_close),
+            Expr::Table {
+                brace_open,
+                entries,
+                brace_close,
+            } => push_tokens!(tokens; brace_open, entries, brace_close),
+            Expr::Function {
+                function_token,
+                body,
+            } => push_tokens!(tokens; function_token, body),
+            Expr::Lambda { at, params, body } => push_tokens!(tokens; at, params, body),
+            Expr::Class { class_token, body } => push_tokens!(tokens; class_token, body),
+            Expr::Delegate {
+                delegate_token,
+                parent,
+                colon,
+                expr,
+            } => push_tokens!(tokens; delegate_token, parent, colon, expr),
+            Expr::Unary { op, expr } => push_tokens!(tokens; op, expr),
+            Expr::Postfix { expr, op } => push_tokens!(tokens; expr, op),
+            Expr::Binary { lhs, op, rhs } => push_tokens!(tokens; lhs, op, rhs),
+            Expr::Ternary {
+                cond,
+                question,
+                then_branch,
+                colon,
+                else_branch,
+            } => push_tokens!(tokens; cond, question, then_branch, colon, else_branch),
+            Expr::Field { expr, dot, name } => push_tokens!(tokens; expr, dot, name),
+            Expr::Index {
+                expr,
+                square_open,
+                index,
+                square_close,
+            } => push_tokens!(tokens; expr, square_open, index, square_close),
+            Expr::Call {
+                expr,
+                paren_open,
+                args,
+                paren_close,
+            } => push_tokens!(tokens; expr, paren_open, args, paren_close),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::{Lexer, LexerError, Token};
+    use crate::parser::Parser;
+
+    macro_rules! assert_round_trip {
+        ($source: expr) => {{
+            let tokens = Lexer::new($source)
+                .collect::<Result<Vec<Token>, LexerError>>()
+                .unwrap();
+            let chunk = Parser::new(tokens).parse().unwrap();
+            assert_eq!(chunk.to_string(), $source);
+        }};
+    }
+
+    #[test]
+    fn round_trip_empty() {
+        assert_round_trip!("");
+        assert_round_trip!("\n\n");
+        assert_round_trip!("  \t ");
+        assert_round_trip!("// only a comment");
+        assert_round_trip!("/* only\na comment */\n");
+    }
+
+    #[test]
+    fn round_trip_statements() {
+        assert_round_trip!("local a = 1;\nlocal   b=2 ,c\n");
+        assert_round_trip!("if(a){b()}else   if (c) d(); else {}\n");
+        assert_round_trip!("for (local i = 0;i<10;i++)\n\tprint( i )");
+        assert_round_trip!("foreach(k ,v in t) {}\nwhile (x) x--\ndo{ }while(y);");
+        assert_round_trip!("switch (x) {\ncase 1 :\n  break\n default:\n}\n");
+        assert_round_trip!("try { throw \"x\" } catch ( e ) { }");
+        assert_round_trip!("enum E { A, B = 2 C = -1 }\nconst X = @\"a\nb\";");
+        assert_round_trip!("function a::b (x, y = 2, ...) { return x; }\n");
+    }
+
+    #[test]
+    fn round_trip_expressions() {
+        assert_round_trip!("a = b ? c : d , e <- @( x ) x*2");
+        assert_round_trip!("t = { a = 1, [ \"b\" ] = 2 \"c\" : [1 2, 3] }");
+        assert_round_trip!("::print( typeof - a.b[ c ]( ) ++ )");
+        assert_round_trip!("x = class extends ::Base </ a = 1 /> {\n  static y = null;\n}\n");
+    }
+
+    #[test]
+    fn round_trip_comments() {
+        assert_round_trip!(
+            r#"// header comment
+
+/* block */ local a = 1 // trailing
+# hash comment
+class Foo { // after brace
+    /** doc */
+    constructor() {} /* after */
+}
+// at the end
+"#
+        );
+    }
+}
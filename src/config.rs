@@ -0,0 +1,413 @@
+//! Formatter options, and the `squirrelfmt.toml` files they are read from.
+//!
+//! A script is formatted with the options in the nearest `squirrelfmt.toml` found by walking up the
+//! directories from it. Options missing from the file keep their default values:
+//!
+//! ```toml
+//! dialect = "3.2"       # or "2.2"
+//! encoding = "utf-8"    # or "windows-1252"
+//! indent_width = 4
+//! use_tabs = false
+//! max_width = 100
+//! line_endings = "auto" # or "lf", "crlf"
+//! brace_style = "k&r"   # or "allman"
+//! ```
+//!
+//! The brace style can also be set separately for declarations, control flow and tables:
+//!
+//! ```toml
+//! brace_style = { declarations = "allman", control_flow = "allman", tables = "k&r" }
+//! ```
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::encoding::Encoding;
+
+/// The name of configuration files.
+pub const FILE_NAME: &str = "squirrelfmt.toml";
+
+/// Options controlling the output of the formatter.
+///
+/// Options may be added in any release, so other crates build configurations with
+/// [`Config::builder`] rather than struct literals.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Config {
+    /// The version of Squirrel the scripts are written in.
+    pub dialect: Dialect,
+    /// How the bytes of scripts map to characters.
+    pub encoding: Encoding,
+    /// The number of columns per level of indentation.
+    pub indent_width: usize,
+    /// Whether to indent with a tab per level instead of spaces. A tab still counts as
+    /// `indent_width` columns towards `max_width`.
+    pub use_tabs: bool,
+    /// The width the formatter tries to keep lines within. Lines can still end up longer, e.g. when
+    /// a single string literal doesn't fit.
+    pub max_width: usize,
+    pub line_endings: LineEndings,
+    pub brace_style: BraceStyles,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            dialect: Dialect::Squirrel3,
+            encoding: Encoding::Utf8,
+            indent_width: 4,
+            use_tabs: false,
+            max_width: 100,
+            line_endings: LineEndings::Auto,
+            brace_style: BraceStyles::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Starts from the default configuration, to change some options of it.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder {
+            config: Config::default(),
+        }
+    }
+
+    /// Parses the contents of a configuration file.
+    pub fn from_toml(text: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Reads a configuration file.
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| ConfigError::Io(path.to_owned(), error))?;
+        Config::from_toml(&text).map_err(|error| ConfigError::Invalid(path.to_owned(), error))
+    }
+
+    /// Reads the configuration file applying to a script or a directory, or returns the default
+    /// configuration if there is none.
+    pub fn for_path(path: &Path) -> Result<Config, ConfigError> {
+        match find(path) {
+            Some(file) => Config::from_file(&file),
+            None => Ok(Config::default()),
+        }
+    }
+}
+
+/// Builds a [`Config`], one option at a time.
+///
+/// ```
+/// use squirrelfmt::config::{BraceStyle, BraceStyles, Config};
+///
+/// let config = Config::builder()
+///     .use_tabs(true)
+///     .brace_style(BraceStyles::all(BraceStyle::Allman))
+///     .build();
+/// assert_eq!(config.max_width, 100);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.config.dialect = dialect;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.config.encoding = encoding;
+        self
+    }
+
+    pub fn indent_width(mut self, indent_width: usize) -> Self {
+        self.config.indent_width = indent_width;
+        self
+    }
+
+    pub fn use_tabs(mut self, use_tabs: bool) -> Self {
+        self.config.use_tabs = use_tabs;
+        self
+    }
+
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.config.max_width = max_width;
+        self
+    }
+
+    pub fn line_endings(mut self, line_endings: LineEndings) -> Self {
+        self.config.line_endings = line_endings;
+        self
+    }
+
+    pub fn brace_style(mut self, brace_style: BraceStyles) -> Self {
+        self.config.brace_style = brace_style;
+        self
+    }
+
+    pub fn build(self) -> Config {
+        self.config
+    }
+}
+
+/// Finds the configuration file applying to a script or a directory, i.e. the closest
+/// `squirrelfmt.toml` in the directory itself or one of its ancestors.
+pub fn find(path: &Path) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    let start = if path.is_dir() { &path } else { path.parent()? };
+    start
+        .ancestors()
+        .map(|directory| directory.join(FILE_NAME))
+        .find(|file| file.is_file())
+}
+
+/// A version of the Squirrel language. Versions only differ from the ones they are named after in
+/// bug fixes and the standard library, which doesn't matter to the formatter.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Dialect {
+    /// Squirrel 2.2, with `delegate`, `parent`, `vargc` and `vargv` as keywords, and without
+    /// `static`, `rawcall`, `__FILE__`, `__LINE__`, lambdas, default parameter values and
+    /// JSON-style table entries.
+    #[serde(rename = "2.2")]
+    Squirrel2,
+    /// Squirrel 3.0 to 3.2.
+    #[default]
+    #[serde(rename = "3.2")]
+    Squirrel3,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "2.2" => Ok(Dialect::Squirrel2),
+            "3.2" => Ok(Dialect::Squirrel3),
+            _ => Err(format!("unknown dialect `{text}`, expected `2.2` or `3.2`")),
+        }
+    }
+}
+
+/// The line endings of the formatted output. Line breaks inside verbatim strings are part of the
+/// string, so they are always left alone.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// Whatever ends the first line of the source, or `\n` if there is only one line.
+    Auto,
+}
+
+/// Where the `{` of a block, a declaration or a table goes.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum BraceStyle {
+    /// At the end of the line it belongs to, e.g. `if (a) {`.
+    #[serde(rename = "k&r")]
+    KAndR,
+    /// On its own line, lined up with the `}`.
+    #[serde(rename = "allman")]
+    Allman,
+}
+
+/// The brace style of each kind of braces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BraceStyles {
+    /// The bodies of functions, classes and enums.
+    pub declarations: BraceStyle,
+    /// The blocks of `if`, loops, `switch` and `try`/`catch`.
+    pub control_flow: BraceStyle,
+    /// Tables assigned to a variable or a slot. Other tables, e.g. arguments, always keep their `{`
+    /// next to what comes before it.
+    pub tables: BraceStyle,
+}
+
+impl BraceStyles {
+    /// The same style for every kind of braces.
+    pub fn all(style: BraceStyle) -> Self {
+        BraceStyles {
+            declarations: style,
+            control_flow: style,
+            tables: style,
+        }
+    }
+}
+
+impl Default for BraceStyles {
+    fn default() -> Self {
+        BraceStyles::all(BraceStyle::KAndR)
+    }
+}
+
+// Either a single style for everything, or a table with one for each kind of braces. Kinds missing
+// from the table keep the default style.
+impl<'de> Deserialize<'de> for BraceStyles {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BraceStylesVisitor;
+
+        impl<'de> Visitor<'de> for BraceStylesVisitor {
+            type Value = BraceStyles;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a brace style or a table of brace styles")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<BraceStyles, E> {
+                let style = BraceStyle::deserialize(de::value::StrDeserializer::new(value))?;
+                Ok(BraceStyles::all(style))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<BraceStyles, A::Error> {
+                #[derive(Deserialize)]
+                #[serde(
+                    remote = "BraceStyles",
+                    default = "BraceStyles::default",
+                    deny_unknown_fields
+                )]
+                struct Fields {
+                    declarations: BraceStyle,
+                    control_flow: BraceStyle,
+                    tables: BraceStyle,
+                }
+                Fields::deserialize(de::value::MapAccessDeserializer::new(map))
+            }
+        }
+
+        deserializer.deserialize_any(BraceStylesVisitor)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A configuration file couldn't be read.
+    Io(PathBuf, io::Error),
+    /// A configuration file isn't valid TOML, or has unknown keys or invalid values.
+    Invalid(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, error) => write!(f, "{}: {error}", path.display()),
+            // TOML errors span multiple lines, with the offending part of the file
+            ConfigError::Invalid(path, error) => write!(f, "{}: {error}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, error) => Some(error),
+            ConfigError::Invalid(_, error) => Some(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+        assert_eq!(
+            Config::from_toml(
+                "encoding = \"latin-1\"\nindent_width = 2\nuse_tabs = true\nmax_width = 80\n\
+                 line_endings = \"crlf\"\nbrace_style = \"allman\"\n"
+            )
+            .unwrap(),
+            Config {
+                dialect: Dialect::Squirrel3,
+                encoding: Encoding::Windows1252,
+                indent_width: 2,
+                use_tabs: true,
+                max_width: 80,
+                line_endings: LineEndings::CrLf,
+                brace_style: BraceStyles::all(BraceStyle::Allman),
+            }
+        );
+    }
+
+    #[test]
+    fn builder() {
+        assert_eq!(Config::builder().build(), Config::default());
+        assert_eq!(
+            Config::builder()
+                .indent_width(2)
+                .max_width(80)
+                .line_endings(LineEndings::Lf)
+                .build(),
+            Config::from_toml("indent_width = 2\nmax_width = 80\nline_endings = \"lf\"").unwrap()
+        );
+    }
+
+    #[test]
+    fn brace_styles() {
+        let config = Config::from_toml(
+            "brace_style = { declarations = \"allman\", control_flow = \"k&r\" }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.brace_style,
+            BraceStyles {
+                declarations: BraceStyle::Allman,
+                control_flow: BraceStyle::KAndR,
+                tables: BraceStyle::KAndR,
+            }
+        );
+
+        let error = Config::from_toml("brace_style = { functions = \"allman\" }").unwrap_err();
+        assert!(error.to_string().contains("unknown field `functions`"));
+        let error = Config::from_toml("brace_style = \"gnu\"").unwrap_err();
+        assert!(error.to_string().contains("unknown variant `gnu`"));
+        assert!(Config::from_toml("brace_style = 1").is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            Config::from_toml("dialect = \"2.2\"").unwrap().dialect,
+            Dialect::Squirrel2
+        );
+        assert!(Config::from_toml("dialect = \"2.3\"").is_err());
+        let error = Config::from_toml("indent = 2").unwrap_err();
+        assert!(error.to_string().contains("unknown field `indent`"));
+        let error = Config::from_toml("line_endings = \"cr\"").unwrap_err();
+        assert!(error.to_string().contains("unknown variant `cr`"));
+        assert!(Config::from_toml("max_width = -1").is_err());
+    }
+
+    #[test]
+    fn discovery() {
+        let root = std::env::temp_dir().join(format!("squirrelfmt-config-{}", std::process::id()));
+        let nested = root.join("a").join("b");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(root.join(FILE_NAME), "max_width = 80").unwrap();
+        std::fs::write(root.join("a").join(FILE_NAME), "max_width = 60").unwrap();
+
+        assert_eq!(
+            find(&nested.join("main.nut")),
+            Some(root.join("a").join(FILE_NAME))
+        );
+        assert_eq!(find(&root.join("main.nut")), Some(root.join(FILE_NAME)));
+        assert_eq!(Config::for_path(&nested).unwrap().max_width, 60);
+
+        std::fs::write(root.join("a").join(FILE_NAME), "max_width = \"wide\"").unwrap();
+        let error = Config::for_path(&nested.join("main.nut")).unwrap_err();
+        assert!(
+            matches!(error, ConfigError::Invalid(path, _) if path == root.join("a").join(FILE_NAME))
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
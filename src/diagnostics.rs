@@ -0,0 +1,204 @@
+use std::fmt::Write as _;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::encoding::{EncodingError, EncodingErrorKind};
+use crate::lexer::{LexerError, LexerErrorKind};
+use crate::line_index::LineIndex;
+use crate::parser::ParseError;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// An error at a position in a script, ready to be shown to a user.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// An explanation of the error, e.g. a language rule that isn't obvious.
+    pub help: Option<&'static str>,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic the way rustc does, with the path, the offending line of the source
+    /// and a caret under the column:
+    ///
+    /// ```text
+    /// error: invalid digit in octal number
+    ///  --> main.nut:1:3
+    ///   |
+    /// 1 | 0080
+    ///   |   ^
+    ///   |
+    ///   = help: ...
+    /// ```
+    ///
+    /// `colors` adds ANSI escape codes for terminals.
+    pub fn render(&self, path: &str, source: &str, colors: bool) -> String {
+        let paint = |color: &'static str| if colors { color } else { "" };
+        let (red, blue, bold, reset) = (paint(RED), paint(BLUE), paint(BOLD), paint(RESET));
+
+        let text = LineIndex::new(source).line_text(self.line);
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // Tabs are kept so that the caret lines up however wide the terminal shows them
+        let padding: String = text
+            .graphemes(true)
+            .take(self.column.saturating_sub(1) as usize)
+            .map(|grapheme| if grapheme == "\t" { '\t' } else { ' ' })
+            .collect();
+
+        let mut output = String::new();
+        let message = &self.message;
+        let (line, column) = (self.line, self.column);
+        writeln!(output, "{red}error{reset}{bold}: {message}{reset}").unwrap();
+        writeln!(output, "{gutter}{blue}-->{reset} {path}:{line}:{column}").unwrap();
+        writeln!(output, "{gutter} {blue}|{reset}").unwrap();
+        writeln!(output, "{blue}{number} |{reset} {text}").unwrap();
+        writeln!(output, "{gutter} {blue}|{reset} {padding}{red}^{reset}").unwrap();
+        if let Some(help) = self.help {
+            writeln!(output, "{gutter} {blue}|{reset}").unwrap();
+            writeln!(output, "{gutter} {blue}={reset} {bold}help{reset}: {help}").unwrap();
+        }
+        output
+    }
+}
+
+impl From<&LexerError> for Diagnostic {
+    fn from(error: &LexerError) -> Self {
+        Diagnostic {
+            message: error.kind.to_string(),
+            help: help(&error.kind),
+            line: error.line,
+            column: error.column,
+        }
+    }
+}
+
+impl From<&EncodingError> for Diagnostic {
+    fn from(error: &EncodingError) -> Self {
+        let help = match error.kind {
+            EncodingErrorKind::InvalidUtf8 => Some(
+                "scripts in another encoding need e.g. `encoding = \"windows-1252\"` in \
+                 `squirrelfmt.toml`",
+            ),
+            EncodingErrorKind::Unencodable(_) => None,
+        };
+        Diagnostic {
+            message: error.kind.to_string(),
+            help,
+            line: error.line,
+            column: error.column,
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        Diagnostic {
+            message: error.kind.to_string(),
+            help: None,
+            line: error.line,
+            column: error.column,
+        }
+    }
+}
+
+fn help(kind: &LexerErrorKind) -> Option<&'static str> {
+    let help = match kind {
+        LexerErrorKind::CharOob => "character literals can only hold ASCII characters",
+        LexerErrorKind::CharTooLong => "use a string for more than one character",
+        LexerErrorKind::InvalidEscape => {
+            "the valid escapes are `\\t`, `\\a`, `\\b`, `\\n`, `\\r`, `\\v`, `\\f`, `\\0`, `\\\\`, \
+             `\\\"`, `\\'`, `\\x`, `\\u` and `\\U`"
+        }
+        LexerErrorKind::InvalidHexEscape => "`\\x`, `\\u` and `\\U` need at least one hex digit",
+        LexerErrorKind::InvalidOctal => {
+            "a number starting with `0` is octal unless its second digit is `8` or `9`, so all of \
+             its digits must be from `0` to `7`"
+        }
+        LexerErrorKind::MissingFloatExponent => "an `e` in a number must be followed by digits",
+        LexerErrorKind::MalformedFloat => {
+            "a number can have one `.` and one exponent, and the `.` must come first"
+        }
+        LexerErrorKind::EmptyHex => "`0x` must be followed by hex digits",
+        LexerErrorKind::IntegerOverflow => {
+            "integers must fit in 64 bits, use a float for larger numbers"
+        }
+        LexerErrorKind::IdentAfterNumber => {
+            "names can't start with a digit, add a space if two tokens were meant"
+        }
+        LexerErrorKind::UnclosedString => {
+            "strings can't span lines, use a verbatim string `@\"...\"` instead"
+        }
+        LexerErrorKind::DoubleDot => "use `.` to access a slot or `...` for variable arguments",
+        _ => return None,
+    };
+    Some(help)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, Token};
+
+    fn render(source: &str, colors: bool) -> String {
+        let error = Lexer::new(source)
+            .collect::<Result<Vec<Token>, LexerError>>()
+            .unwrap_err();
+        Diagnostic::from(&error).render("main.nut", source, colors)
+    }
+
+    #[test]
+    fn snippet() {
+        assert_eq!(
+            render("local a = 1\nlocal b = 0080\n", false),
+            "error: invalid digit in octal number\n \
+             --> main.nut:2:13\n  \
+             |\n\
+             2 | local b = 0080\n  \
+             |             ^\n  \
+             |\n  \
+             = help: a number starting with `0` is octal unless its second digit is `8` or `9`, so \
+             all of its digits must be from `0` to `7`\n"
+        );
+    }
+
+    #[test]
+    fn tabs_and_graphemes() {
+        assert_eq!(
+            render("\t\"Möglichkeit\" $", false),
+            "error: unexpected character\n \
+             --> main.nut:1:16\n  \
+             |\n\
+             1 | \t\"Möglichkeit\" $\n  \
+             | \t              ^\n"
+        );
+    }
+
+    #[test]
+    fn colors() {
+        let rendered = render("/*", true);
+        assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m\x1b[1m: unclosed multi-line comment"));
+        assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+    }
+
+    #[test]
+    fn parse_error() {
+        let tokens = Lexer::new("local = 1")
+            .collect::<Result<Vec<Token>, LexerError>>()
+            .unwrap();
+        let error = crate::parser::Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            Diagnostic::from(&error).render("main.nut", "local = 1", false),
+            "error: expected an identifier\n \
+             --> main.nut:1:7\n  \
+             |\n\
+             1 | local = 1\n  \
+             |       ^\n"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Decoding scripts from bytes, and encoding their formatted text back the same way.
+//!
+//! Scripts are UTF-8, optionally starting with a byte order mark. Older scripts, e.g. the ones of
+//! many game mods, are often Windows-1252 instead, which needs `encoding = "windows-1252"` in the
+//! configuration. A byte order mark always means UTF-8, whatever the configuration says.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::line_index::LineIndex;
+
+/// The UTF-8 byte order mark.
+pub const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// How the bytes of a script map to characters.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Encoding {
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    /// Windows-1252, also used for Latin-1 (ISO-8859-1) scripts. The two only differ in bytes
+    /// `0x80` to `0x9F`, which are control characters in Latin-1 and hardly ever used. Each byte
+    /// decodes to a character which encodes back to it, so the bytes of a script never change
+    /// other than by formatting.
+    #[serde(rename = "windows-1252", alias = "latin-1")]
+    Windows1252,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "utf-8" => Ok(Encoding::Utf8),
+            "windows-1252" | "latin-1" => Ok(Encoding::Windows1252),
+            _ => Err(format!(
+                "unknown encoding `{text}`, expected `utf-8` or `windows-1252`"
+            )),
+        }
+    }
+}
+
+/// The text of a script, along with how to encode it back to bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoded<'a> {
+    /// The text, without the byte order mark.
+    pub text: Cow<'a, str>,
+    pub encoding: Encoding,
+    /// Whether the script started with a byte order mark.
+    pub bom: bool,
+}
+
+impl Decoded<'_> {
+    /// Encodes text the way the script was, e.g. its formatted version. Characters which aren't in
+    /// the encoding are errors, positioned in `text`.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, EncodingError> {
+        let mut bytes = Vec::with_capacity(BOM.len() + text.len());
+        if self.bom {
+            bytes.extend_from_slice(BOM);
+        }
+        match self.encoding {
+            Encoding::Utf8 => bytes.extend_from_slice(text.as_bytes()),
+            Encoding::Windows1252 => {
+                for (offset, character) in text.char_indices() {
+                    let Some(byte) = windows_1252_byte(character) else {
+                        let kind = EncodingErrorKind::Unencodable(character);
+                        return Err(EncodingError::at(kind, text, offset));
+                    };
+                    bytes.push(byte);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// Decodes a script, stripping its byte order mark if it has one.
+pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<Decoded<'_>, EncodingError> {
+    let (rest, bom) = match bytes.strip_prefix(BOM) {
+        Some(rest) => (rest, true),
+        None => (bytes, false),
+    };
+    let encoding = if bom { Encoding::Utf8 } else { encoding };
+    let text = match encoding {
+        Encoding::Utf8 => std::str::from_utf8(rest)
+            .map_err(|error| {
+                // Everything before the invalid byte is valid
+                let valid = std::str::from_utf8(&rest[..error.valid_up_to()]).unwrap();
+                let mut error =
+                    EncodingError::at(EncodingErrorKind::InvalidUtf8, valid, valid.len());
+                error.offset += bytes.len() - rest.len();
+                error
+            })?
+            .into(),
+        Encoding::Windows1252 if rest.is_ascii() => std::str::from_utf8(rest).unwrap().into(),
+        Encoding::Windows1252 => rest.iter().map(|&byte| windows_1252_char(byte)).collect(),
+    };
+    Ok(Decoded {
+        text,
+        encoding,
+        bom,
+    })
+}
+
+// The characters of bytes 0x80 to 0x9F in Windows-1252. The five bytes it leaves undefined map to
+// the control characters of the same codes, like in Latin-1.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+fn windows_1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
+
+fn windows_1252_byte(character: char) -> Option<u8> {
+    match u32::from(character) {
+        code @ (0..=0x7F | 0xA0..=0xFF) => Some(code as u8),
+        _ => WINDOWS_1252
+            .iter()
+            .position(|&other| other == character)
+            .map(|index| 0x80 + index as u8),
+    }
+}
+
+/// Why a script couldn't be decoded or encoded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodingError {
+    pub kind: EncodingErrorKind,
+    pub line: u32,
+    pub column: u32,
+    /// The offset of the byte the error was detected at, in the bytes being decoded or the text
+    /// being encoded.
+    pub offset: usize,
+}
+
+impl EncodingError {
+    // An error at the end of `text`, where the part of a script before the error ends
+    fn at(kind: EncodingErrorKind, text: &str, offset: usize) -> Self {
+        let (line, column) = LineIndex::new(text).line_column(offset);
+        EncodingError {
+            kind,
+            line,
+            column,
+            offset,
+        }
+    }
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}:{}", self.kind, self.line, self.column)
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EncodingErrorKind {
+    /// The script is meant to be UTF-8, but isn't.
+    InvalidUtf8,
+    /// A character can't be encoded in Windows-1252.
+    Unencodable(char),
+}
+
+impl fmt::Display for EncodingErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingErrorKind::InvalidUtf8 => f.write_str("invalid UTF-8"),
+            EncodingErrorKind::Unencodable(character) => {
+                write!(f, "`{character}` can't be encoded in Windows-1252")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8() {
+        let decoded = decode("local ä = 1\n".as_bytes(), Encoding::Utf8).unwrap();
+        assert!(matches!(decoded.text, Cow::Borrowed("local ä = 1\n")));
+        assert!(!decoded.bom);
+        assert_eq!(
+            decoded.encode("local ä = 2\n").unwrap(),
+            "local ä = 2\n".as_bytes()
+        );
+    }
+
+    #[test]
+    fn bom() {
+        let decoded = decode(b"\xEF\xBB\xBFx=1", Encoding::Windows1252).unwrap();
+        assert_eq!(decoded.text, "x=1");
+        assert_eq!(decoded.encoding, Encoding::Utf8);
+        assert!(decoded.bom);
+        assert_eq!(decoded.encode("x = 1\n").unwrap(), b"\xEF\xBB\xBFx = 1\n");
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let error = decode(b"a = 1\nb = \"\xE4\"", Encoding::Utf8).unwrap_err();
+        assert_eq!(
+            error,
+            EncodingError {
+                kind: EncodingErrorKind::InvalidUtf8,
+                line: 2,
+                column: 6,
+                offset: 11,
+            }
+        );
+        assert_eq!(error.to_string(), "invalid UTF-8 at 2:6");
+
+        // Offsets count the byte order mark, columns don't
+        let error = decode(b"\xEF\xBB\xBFa\xFF", Encoding::Utf8).unwrap_err();
+        assert_eq!((error.line, error.column, error.offset), (1, 2, 4));
+    }
+
+    #[test]
+    fn windows_1252() {
+        let bytes = b"print(\"\xC4rger \x80 \x93quoted\x94 \x81\")";
+        let decoded = decode(bytes, Encoding::Windows1252).unwrap();
+        assert_eq!(
+            decoded.text,
+            "print(\"Ärger € \u{201C}quoted\u{201D} \u{81}\")"
+        );
+        assert_eq!(decoded.encode(&decoded.text).unwrap(), bytes);
+
+        // Every byte survives a round trip
+        let bytes: Vec<u8> = (0..=255).collect();
+        let decoded = decode(&bytes, Encoding::Windows1252).unwrap();
+        assert_eq!(decoded.encode(&decoded.text).unwrap(), bytes);
+
+        let error = decoded.encode("a\nb = \"✓\"").unwrap_err();
+        assert_eq!(error.kind, EncodingErrorKind::Unencodable('✓'));
+        assert_eq!((error.line, error.column), (2, 6));
+    }
+}
@@ -0,0 +1,1665 @@
+//! Pretty-printing of the syntax tree.
+//!
+//! The tree is lowered into a [`Doc`], which is then laid out within the configured line width.
+//! Comments stay next to the tokens they were attached to, and a single blank line is kept wherever
+//! the source had one or more between statements.
+//!
+//! Squirrel is sensitive to newlines in a few places: a `[` or a postfix `++`/`--` on a new line
+//! starts a new expression, and so does anything on the line after a `return`. The lowering never
+//! puts a line break there, and breaks lines after binary operators rather than before them.
+//!
+//! Statements can be excluded from formatting with comments on lines of their own:
+//! `// squirrelfmt: skip` keeps the next statement as it is, and everything from
+//! `// squirrelfmt: off` up to `// squirrelfmt: on` (or the end of the block) is kept too.
+
+mod doc;
+
+use std::cell::Cell;
+use std::ops::{Range, RangeInclusive};
+
+use crate::ast::*;
+use crate::config::{BraceStyle, Config, LineEndings};
+use crate::lexer::{LineEnding, Token, TokenKind};
+use crate::line_index::LineIndex;
+use crate::parser::binary_precedence;
+use doc::{Doc, broken_group, docs, group, hug, indent, indent_if_break, nil};
+
+/// Formats a parsed script. The output always ends with a newline, unless it is empty.
+pub fn format(chunk: &Chunk<'_>, config: &Config) -> String {
+    let line_ending = line_ending(chunk, config);
+    let doc = Formatter::new(config, None).lower_chunk(chunk);
+    let mut output = doc::print(doc, config, line_ending);
+    if !output.is_empty() {
+        output.push_str(line_ending.as_str());
+    }
+    output
+}
+
+/// Formats only the statements covering a range of lines (1-based and inclusive), and returns the
+/// script with everything else left as it was.
+///
+/// The statements are the smallest set of complete statements covering the range which take up
+/// lines of their own: the statements of the innermost block containing the range, or else the
+/// statement containing that block, and so on. Statements sharing a line with one of them are
+/// formatted too. If the range only covers blank lines or comments between statements, nothing
+/// changes.
+///
+/// Byte offsets, e.g. from a `Token`, can be turned into lines with
+/// [`LineIndex`](crate::line_index::LineIndex).
+pub fn format_range(chunk: &Chunk<'_>, config: &Config, lines: RangeInclusive<u32>) -> String {
+    let source = chunk.to_string();
+    let formatter = Formatter::new(config, Some(lines));
+    let doc = formatter.lower_chunk(chunk);
+    let Some((first_line, last_line)) = formatter.selection.get() else {
+        return source;
+    };
+
+    let (formatted, marks) = doc::print_marked(doc, config, line_ending(chunk, config));
+    let formatted_index = LineIndex::new(&formatted);
+    let start = formatted_index.line_column(marks[0]).0;
+    let end = formatted_index.line_column(marks[1]).0;
+    let replacement =
+        &formatted[formatted_index.offset(start, 1)..formatted_index.offset(end, u32::MAX)];
+
+    // Whole lines are replaced, without their line endings
+    let source_index = LineIndex::new(&source);
+    let replaced = source_index.offset(first_line, 1)..source_index.offset(last_line, u32::MAX);
+    let mut output = source.clone();
+    output.replace_range(replaced, replacement);
+    output
+}
+
+fn line_ending(chunk: &Chunk<'_>, config: &Config) -> LineEnding {
+    match config.line_endings {
+        LineEndings::Lf => LineEnding::Lf,
+        LineEndings::CrLf => LineEnding::CrLf,
+        LineEndings::Auto => first_line_ending(chunk).unwrap_or(LineEnding::Lf),
+    }
+}
+
+fn first_line_ending(chunk: &Chunk<'_>) -> Option<LineEnding> {
+    let mut tokens = Vec::new();
+    chunk.tokens(&mut tokens);
+    tokens
+        .iter()
+        .flat_map(|token| token.leading_trivia.iter().chain(&token.trailing_trivia))
+        .find_map(|trivia| match trivia.kind {
+            TokenKind::Newline(ending) => Some(ending),
+            _ => None,
+        })
+}
+
+struct Formatter<'a> {
+    config: &'a Config,
+    // The lines to format for `format_range`
+    range: Option<RangeInclusive<u32>>,
+    // The first and last line of the statements marked for `format_range`, once they were found
+    selection: Cell<Option<(u32, u32)>>,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(config: &'a Config, range: Option<RangeInclusive<u32>>) -> Self {
+        Formatter {
+            config,
+            range,
+            selection: Cell::new(None),
+        }
+    }
+
+    fn lower_chunk(&self, chunk: &Chunk<'_>) -> Doc {
+        let mut docs = vec![self.stmts(&chunk.stmts, true)];
+        if let Some(comments) = dangling_comments(&chunk.eof) {
+            if !chunk.stmts.is_empty() {
+                docs.push(Doc::HardLine);
+                if blank_line_before(&chunk.eof) {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            docs.push(comments);
+        }
+        Doc::Concat(docs)
+    }
+
+    // Puts `body` on its own indented lines between `open` and `close`, along with the comments
+    // before `close`. An empty body without comments gives `{}`.
+    fn braced(
+        &self,
+        open: &TokenReference<'_>,
+        body: Option<Doc>,
+        close: &TokenReference<'_>,
+    ) -> Doc {
+        let comments = dangling_comments(close);
+        if body.is_none() && comments.is_none() {
+            return docs![token(open), bare_token(close)];
+        }
+
+        let mut inner = vec![Doc::HardLine];
+        let has_body = body.is_some();
+        inner.extend(body);
+        if let Some(comments) = comments {
+            if has_body {
+                inner.push(Doc::HardLine);
+                if blank_line_before(close) {
+                    inner.push(Doc::HardLine);
+                }
+            }
+            inner.push(comments);
+        }
+        docs![
+            token(open),
+            indent(Doc::Concat(inner)),
+            Doc::HardLine,
+            bare_token(close)
+        ]
+    }
+
+    // Statements on their own lines. Those in a `squirrelfmt: off` region or after a
+    // `squirrelfmt: skip` are kept as they are. `top_level` is set for the statements of the chunk.
+    fn stmts(&self, stmts: &[Stmt<'_>], top_level: bool) -> Doc {
+        let mut items = Vec::new();
+        let mut i = 0;
+        while i < stmts.len() {
+            let first = first_token(&stmts[i]);
+            let end = match directives(first).last() {
+                Some(Directive::Off) => stmts[i + 1..]
+                    .iter()
+                    .position(|stmt| directives(first_token(stmt)).any(|d| d == Directive::On))
+                    .map_or(stmts.len(), |offset| i + 1 + offset),
+                Some(Directive::Skip) => i + 1,
+                Some(Directive::On) | None => {
+                    items.push((&stmts[i..=i], self.stmt(&stmts[i])));
+                    i += 1;
+                    continue;
+                }
+            };
+            items.push((&stmts[i..end], verbatim(&stmts[i..end])));
+            i = end;
+        }
+
+        // Nested statements were lowered first, so that the innermost ones covering the range win
+        if let Some(range) = &self.range
+            && self.selection.get().is_none()
+        {
+            self.select(&mut items, range, top_level);
+        }
+        lines(
+            items
+                .into_iter()
+                .map(|(stmts, doc)| (first_token(&stmts[0]), doc)),
+        )
+    }
+
+    // Marks the items covering `range` for `format_range`, unless they share their first or last
+    // line with something else, like the `{` and `}` of a block. The statements of the chunk are
+    // marked regardless, since there is nothing around them.
+    fn select(
+        &self,
+        items: &mut [(&[Stmt<'_>], Doc)],
+        range: &RangeInclusive<u32>,
+        top_level: bool,
+    ) {
+        let overlaps = |(stmts, _): &(&[Stmt], Doc)| {
+            let lines = stmt_lines(stmts);
+            lines.start <= *range.end() && lines.end > *range.start()
+        };
+        let (Some(mut first), Some(mut last)) = (
+            items.iter().position(overlaps),
+            items.iter().rposition(overlaps),
+        ) else {
+            return;
+        };
+
+        // Statements sharing a line are formatted together
+        while first > 0 && !starts_line(items[first].0) {
+            first -= 1;
+        }
+        while last + 1 < items.len() && !ends_line(items[last].0) {
+            last += 1;
+        }
+
+        let lines = stmt_lines(items[first].0).start..stmt_lines(items[last].0).end;
+        let covers = lines.start <= *range.start() && lines.end > *range.end();
+        let own_lines = starts_line(items[first].0) && ends_line(items[last].0);
+        if !(top_level || covers && own_lines) {
+            return;
+        }
+
+        let doc = std::mem::replace(&mut items[first].1, nil());
+        items[first].1 = docs![Doc::Mark, doc];
+        let doc = std::mem::replace(&mut items[last].1, nil());
+        items[last].1 = docs![doc, Doc::Mark];
+        self.selection.set(Some((lines.start, lines.end - 1)));
+    }
+
+    fn block(&self, block: &Block<'_>) -> Doc {
+        let body = (!block.stmts.is_empty()).then(|| self.stmts(&block.stmts, false));
+        self.braced(&block.brace_open, body, &block.brace_close)
+    }
+
+    // The body of a control flow statement like a loop or an `if`.
+    fn body(&self, stmt: &Stmt<'_>) -> Doc {
+        self.styled_body(stmt, self.config.brace_style.control_flow)
+    }
+
+    // The body of a statement or a function. Blocks start after the header in the given style and
+    // anything else goes on its own indented line, except for an empty statement which stays right
+    // after the header.
+    fn styled_body(&self, stmt: &Stmt<'_>, style: BraceStyle) -> Doc {
+        match &stmt.kind {
+            StmtKind::Block(block) => docs![
+                before_brace(style, block.stmts.is_empty(), &block.brace_close),
+                self.stmt(stmt)
+            ],
+            StmtKind::Empty => self.stmt(stmt),
+            _ => indent(docs![Doc::HardLine, self.stmt(stmt)]),
+        }
+    }
+
+    // What goes between the body of a statement and a keyword continuing it, like `else`.
+    fn after_body(&self, stmt: &Stmt<'_>) -> Doc {
+        if is_block(stmt) && self.config.brace_style.control_flow == BraceStyle::KAndR {
+            " ".into()
+        } else {
+            Doc::HardLine
+        }
+    }
+
+    // A parenthesized condition, e.g. of an `if` or `while`.
+    fn condition(
+        &self,
+        paren_open: &TokenReference,
+        cond: &Expr,
+        paren_close: &TokenReference,
+    ) -> Doc {
+        let cond = match cond {
+            Expr::Binary { lhs, op, rhs } if binary_precedence(&op.kind).is_some() => {
+                self.binary(lhs, op, rhs, false)
+            }
+            _ => self.expr(cond),
+        };
+        group(docs![
+            token(paren_open),
+            indent(docs![Doc::SoftLine, cond]),
+            Doc::SoftLine,
+            token(paren_close)
+        ])
+    }
+
+    fn stmt(&self, stmt: &Stmt<'_>) -> Doc {
+        docs![self.stmt_kind(&stmt.kind), optional_token(&stmt.semicolon)]
+    }
+
+    fn stmt_kind(&self, kind: &StmtKind<'_>) -> Doc {
+        match kind {
+            StmtKind::Empty => nil(),
+            StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::Block(block) => self.block(block),
+
+            StmtKind::If {
+                if_token,
+                paren_open,
+                cond,
+                paren_close,
+                body,
+                else_branch,
+            } => {
+                let mut docs = vec![
+                    token(if_token),
+                    " ".into(),
+                    self.condition(paren_open, cond, paren_close),
+                    self.body(body),
+                ];
+                if let Some((else_token, else_body)) = else_branch {
+                    docs.push(self.after_body(body));
+                    docs.push(token(else_token));
+                    docs.push(match else_body.kind {
+                        StmtKind::If { .. } => docs![" ", self.stmt(else_body)],
+                        _ => self.body(else_body),
+                    });
+                }
+                Doc::Concat(docs)
+            }
+
+            StmtKind::While {
+                while_token,
+                paren_open,
+                cond,
+                paren_close,
+                body,
+            } => docs![
+                token(while_token),
+                " ",
+                self.condition(paren_open, cond, paren_close),
+                self.body(body)
+            ],
+
+            StmtKind::DoWhile {
+                do_token,
+                body,
+                while_token,
+                paren_open,
+                cond,
+                paren_close,
+            } => docs![
+                token(do_token),
+                self.body(body),
+                self.after_body(body),
+                token(while_token),
+                " ",
+                self.condition(paren_open, cond, paren_close)
+            ],
+
+            StmtKind::For {
+                for_token,
+                paren_open,
+                init,
+                first_semicolon,
+                cond,
+                second_semicolon,
+                step,
+                paren_close,
+                body,
+            } => {
+                let init = match init {
+                    Some(ForInit::Local(local)) => self.local(local),
+                    Some(ForInit::Expr(expr)) => self.expr(expr),
+                    None => nil(),
+                };
+                let clause = |expr: &Option<Expr>| match expr {
+                    Some(expr) => docs![Doc::Line, self.expr(expr)],
+                    None => nil(),
+                };
+                let header = group(docs![
+                    token(paren_open),
+                    indent(docs![
+                        Doc::SoftLine,
+                        init,
+                        token(first_semicolon),
+                        clause(cond),
+                        token(second_semicolon),
+                        clause(step)
+                    ]),
+                    Doc::SoftLine,
+                    token(paren_close)
+                ]);
+                docs![token(for_token), " ", header, self.body(body)]
+            }
+
+            StmtKind::Foreach {
+                foreach_token,
+                paren_open,
+                key,
+                value,
+                in_token,
+                iterable,
+                paren_close,
+                body,
+            } => {
+                let key = match key {
+                    Some((key, comma)) => docs![token(key), token(comma), " "],
+                    None => nil(),
+                };
+                docs![
+                    token(foreach_token),
+                    " ",
+                    token(paren_open),
+                    key,
+                    token(value),
+                    " ",
+                    token(in_token),
+                    " ",
+                    self.expr(iterable),
+                    token(paren_close),
+                    self.body(body)
+                ]
+            }
+
+            StmtKind::Switch {
+                switch_token,
+                paren_open,
+                subject,
+                paren_close,
+                brace_open,
+                cases,
+                brace_close,
+            } => {
+                let body = (!cases.is_empty()).then(|| {
+                    lines(
+                        cases
+                            .iter()
+                            .map(|case| (first_token(&case.label), self.switch_case(case))),
+                    )
+                });
+                docs![
+                    token(switch_token),
+                    " ",
+                    self.condition(paren_open, subject, paren_close),
+                    before_brace(
+                        self.config.brace_style.control_flow,
+                        cases.is_empty(),
+                        brace_close
+                    ),
+                    self.braced(brace_open, body, brace_close)
+                ]
+            }
+
+            StmtKind::Local(local) => self.local(local),
+
+            StmtKind::LocalFunction {
+                local_token,
+                function_token,
+                name,
+                body,
+            } => docs![
+                token(local_token),
+                " ",
+                token(function_token),
+                " ",
+                token(name),
+                self.function_body(body)
+            ],
+
+            StmtKind::Function {
+                function_token,
+                name,
+                body,
+            } => {
+                let mut docs = vec![token(function_token), " ".into(), token(&name.first)];
+                for (scope, part) in &name.rest {
+                    docs.push(token(scope));
+                    docs.push(token(part));
+                }
+                docs.push(self.function_body(body));
+                Doc::Concat(docs)
+            }
+
+            StmtKind::Class {
+                class_token,
+                name,
+                body,
+            } => docs![
+                token(class_token),
+                " ",
+                self.expr(name),
+                self.class_body(body)
+            ],
+
+            StmtKind::Enum {
+                enum_token,
+                name,
+                brace_open,
+                members,
+                brace_close,
+            } => docs![
+                token(enum_token),
+                " ",
+                token(name),
+                " ",
+                self.table_like(
+                    brace_open,
+                    members,
+                    brace_close,
+                    Self::enum_member,
+                    self.config.brace_style.declarations
+                )
+            ],
+
+            StmtKind::Const {
+                const_token,
+                name,
+                eq,
+                value,
+            } => docs![
+                token(const_token),
+                " ",
+                token(name),
+                " ",
+                token(eq),
+                " ",
+                self.expr(value)
+            ],
+
+            StmtKind::Return { keyword, value } => match value {
+                Some(value) => docs![token(keyword), " ", self.expr(value)],
+                None => token(keyword),
+            },
+
+            StmtKind::Break(token) | StmtKind::Continue(token) => self::token(token),
+
+            StmtKind::Throw { throw_token, value } => {
+                docs![token(throw_token), " ", self.expr(value)]
+            }
+
+            StmtKind::Try {
+                try_token,
+                body,
+                catch_token,
+                paren_open,
+                binding,
+                paren_close,
+                catch_body,
+            } => docs![
+                token(try_token),
+                self.body(body),
+                self.after_body(body),
+                token(catch_token),
+                " ",
+                token(paren_open),
+                token(binding),
+                token(paren_close),
+                self.body(catch_body)
+            ],
+        }
+    }
+
+    fn switch_case(&self, case: &SwitchCase<'_>) -> Doc {
+        let label = match &case.label {
+            CaseLabel::Case(case_token, value) => docs![token(case_token), " ", self.expr(value)],
+            CaseLabel::Default(default_token) => token(default_token),
+        };
+        let body = match &case.stmts[..] {
+            [] => nil(),
+            [stmt] if is_block(stmt) => self.body(stmt),
+            stmts => indent(docs![Doc::HardLine, self.stmts(stmts, false)]),
+        };
+        docs![label, token(&case.colon), body]
+    }
+
+    fn local(&self, local: &Local<'_>) -> Doc {
+        let vars = self.list_items(&local.vars, Self::local_var);
+        // Only indent when there is something to break between, so that the body of e.g. a function
+        // assigned to a single variable is indented like the body of a function statement
+        let vars = if local.vars.len() > 1 {
+            indent(vars)
+        } else {
+            vars
+        };
+        group(docs![token(&local.local_token), " ", vars])
+    }
+
+    fn local_var(&self, var: &LocalVar<'_>) -> Doc {
+        match &var.init {
+            Some((eq, value)) => docs![token(&var.name), " ", token(eq), " ", self.value(value)],
+            None => token(&var.name),
+        }
+    }
+
+    fn enum_member(&self, member: &EnumMember<'_>) -> Doc {
+        match &member.value {
+            Some((eq, value)) => docs![token(&member.name), " ", token(eq), " ", self.expr(value)],
+            None => token(&member.name),
+        }
+    }
+
+    fn function_body(&self, body: &FunctionBody<'_>) -> Doc {
+        docs![
+            self.params(&body.params),
+            self.styled_body(&body.body, self.config.brace_style.declarations)
+        ]
+    }
+
+    fn params(&self, params: &Params<'_>) -> Doc {
+        self.list(
+            &params.paren_open,
+            &params.params,
+            &params.paren_close,
+            Self::param,
+            false,
+        )
+    }
+
+    fn param(&self, param: &Param<'_>) -> Doc {
+        match param {
+            Param::Named {
+                name,
+                default: Some((eq, value)),
+            } => docs![token(name), " ", token(eq), " ", self.expr(value)],
+            Param::Named {
+                name,
+                default: None,
+            } => token(name),
+            Param::Varargs(ellipsis) => token(ellipsis),
+        }
+    }
+
+    // Everything after `class` and the name of a class, starting with a space.
+    fn class_body(&self, class: &ClassBody<'_>) -> Doc {
+        let mut docs = Vec::new();
+        if let Some((extends_token, base)) = &class.extends {
+            docs.extend([
+                " ".into(),
+                token(extends_token),
+                " ".into(),
+                self.expr(base),
+            ]);
+        }
+        if let Some(attributes) = &class.attributes {
+            docs.extend([" ".into(), self.attributes(attributes)]);
+        }
+
+        let members = class
+            .members
+            .iter()
+            .map(|member| (first_token(member), self.class_member(member)));
+        let body = (!class.members.is_empty()).then(|| lines(members));
+        docs.extend([
+            before_brace(
+                self.config.brace_style.declarations,
+                class.members.is_empty(),
+                &class.brace_close,
+            ),
+            self.braced(&class.brace_open, body, &class.brace_close),
+        ]);
+        Doc::Concat(docs)
+    }
+
+    fn class_member(&self, member: &ClassMember<'_>) -> Doc {
+        let mut docs = Vec::new();
+        if let Some(attributes) = &member.attributes {
+            docs.extend([self.attributes(attributes), Doc::HardLine]);
+        }
+        if let Some(static_token) = &member.static_token {
+            docs.extend([token(static_token), " ".into()]);
+        }
+        docs.extend([
+            self.table_entry(&member.entry),
+            optional_token(&member.separator),
+        ]);
+        Doc::Concat(docs)
+    }
+
+    fn attributes(&self, attributes: &Attributes<'_>) -> Doc {
+        group(docs![
+            token(&attributes.open),
+            indent(docs![
+                Doc::Line,
+                self.list_items(&attributes.entries, Self::table_entry)
+            ]),
+            Doc::Line,
+            token(&attributes.close)
+        ])
+    }
+
+    fn table_entry(&self, entry: &TableEntry<'_>) -> Doc {
+        match entry {
+            TableEntry::Field { name, eq, value } => {
+                docs![token(name), " ", token(eq), " ", self.value(value)]
+            }
+            TableEntry::Computed {
+                square_open,
+                key,
+                square_close,
+                eq,
+                value,
+            } => docs![
+                token(square_open),
+                self.expr(key),
+                token(square_close),
+                " ",
+                token(eq),
+                " ",
+                self.value(value)
+            ],
+            TableEntry::Json { key, colon, value } => {
+                docs![token(key), token(colon), " ", self.value(value)]
+            }
+            TableEntry::Function {
+                function_token,
+                name,
+                body,
+            } => docs![
+                token(function_token),
+                " ",
+                token(name),
+                self.function_body(body)
+            ],
+            TableEntry::Constructor {
+                constructor_token,
+                body,
+            } => docs![token(constructor_token), self.function_body(body)],
+        }
+    }
+
+    // The items of a list with their separators, with a line break opportunity after each of them.
+    fn list_items<T: Node>(&self, items: &Punctuated<T>, lower: fn(&Self, &T) -> Doc) -> Doc {
+        let mut docs = Vec::new();
+        for (i, (item, separator)) in items.iter().enumerate() {
+            docs.push(lower(self, item));
+            docs.push(optional_token(separator));
+            if let Some((next, _)) = items.get(i + 1) {
+                docs.push(match separator {
+                    None if continues_previous_item(next) => docs![Doc::BreakParent, Doc::HardLine],
+                    _ => Doc::Line,
+                });
+            }
+        }
+        Doc::Concat(docs)
+    }
+
+    // A delimited list, without the group around it. Its items go on one line if they fit,
+    // otherwise each of them goes on its own indented line. With `spaced`, a flat list has spaces
+    // inside the delimiters, like `{ a = 1 }`.
+    fn list_contents<T: Node>(
+        &self,
+        open: &TokenReference,
+        items: &Punctuated<T>,
+        close: &TokenReference,
+        lower: fn(&Self, &T) -> Doc,
+        spaced: bool,
+    ) -> Doc {
+        let line = || if spaced { Doc::Line } else { Doc::SoftLine };
+        let mut inner = vec![line(), self.list_items(items, lower)];
+        if let Some(comments) = dangling_comments(close) {
+            inner.extend([Doc::BreakParent, Doc::HardLine, comments]);
+        }
+        docs![
+            token(open),
+            indent_if_break(Doc::Concat(inner)),
+            line(),
+            bare_token(close)
+        ]
+    }
+
+    fn list<T: Node>(
+        &self,
+        open: &TokenReference,
+        items: &Punctuated<T>,
+        close: &TokenReference,
+        lower: fn(&Self, &T) -> Doc,
+        spaced: bool,
+    ) -> Doc {
+        if items.is_empty() {
+            return self.braced(open, None, close);
+        }
+        group(self.list_contents(open, items, close, lower, spaced))
+    }
+
+    // Tables and enums, which are kept expanded if the source had a line break after the `{`. The `{`
+    // of an expanded table goes on its own line in the Allman style.
+    fn table_like<T: Node>(
+        &self,
+        brace_open: &TokenReference,
+        entries: &Punctuated<T>,
+        brace_close: &TokenReference,
+        lower: fn(&Self, &T) -> Doc,
+        style: BraceStyle,
+    ) -> Doc {
+        if entries.is_empty() {
+            return self.braced(brace_open, None, brace_close);
+        }
+        let mut contents = self.list_contents(brace_open, entries, brace_close, lower, true);
+        if style == BraceStyle::Allman {
+            // The space before the `{` is trimmed from the end of the line
+            contents = docs![Doc::SoftLine, contents];
+        }
+        let expanded = brace_open
+            .trailing_trivia
+            .last()
+            .is_some_and(|trivia| matches!(trivia.kind, TokenKind::Newline(_)));
+        if expanded {
+            broken_group(contents)
+        } else {
+            group(contents)
+        }
+    }
+
+    fn arguments(
+        &self,
+        paren_open: &TokenReference,
+        args: &Punctuated<Expr>,
+        paren_close: &TokenReference,
+    ) -> Doc {
+        match args.split_last() {
+            Some(((last, _), rest))
+                if is_huggable(last) && !rest.iter().any(|(arg, _)| is_huggable(arg)) =>
+            {
+                hug(self.list_contents(paren_open, args, paren_close, Self::expr, false))
+            }
+            _ => self.list(paren_open, args, paren_close, Self::expr, false),
+        }
+    }
+
+    // A chain of binary operations with the same precedence, e.g. `a + b - c`, which breaks after
+    // every operator at once. The operands after the first are indented, unless `indented` is
+    // false.
+    fn binary(
+        &self,
+        lhs: &Expr<'_>,
+        op: &TokenReference<'_>,
+        rhs: &Expr<'_>,
+        indented: bool,
+    ) -> Doc {
+        let precedence = binary_precedence(&op.kind);
+        let mut operations = vec![(op, rhs)];
+        let mut first = lhs;
+        while let Expr::Binary { lhs, op, rhs } = first
+            && binary_precedence(&op.kind) == precedence
+        {
+            operations.push((op, rhs));
+            first = lhs;
+        }
+
+        let rest = operations
+            .into_iter()
+            .rev()
+            .map(|(op, rhs)| docs![" ", token(op), Doc::Line, self.expr(rhs)])
+            .collect();
+        let rest = Doc::Concat(rest);
+        group(docs![
+            self.expr(first),
+            if indented { indent(rest) } else { rest }
+        ])
+    }
+
+    // The value of an assignment, a variable or a slot, where a table is laid out like a block.
+    fn value(&self, value: &Expr<'_>) -> Doc {
+        match value {
+            Expr::Table {
+                brace_open,
+                entries,
+                brace_close,
+            } => self.table_like(
+                brace_open,
+                entries,
+                brace_close,
+                Self::table_entry,
+                self.config.brace_style.tables,
+            ),
+            _ => self.expr(value),
+        }
+    }
+
+    fn expr(&self, expr: &Expr<'_>) -> Doc {
+        match expr {
+            Expr::Atom(token) => self::token(token),
+            Expr::Root { scope, name } => docs![token(scope), token(name)],
+            Expr::Paren {
+                paren_open,
+                expr,
+                paren_close,
+            } => docs![token(paren_open), self.expr(expr), token(paren_close)],
+            Expr::Array {
+                square_open,
+                items,
+                square_close,
+            } => self.list(square_open, items, square_close, Self::expr, false),
+            Expr::Table {
+                brace_open,
+                entries,
+                brace_close,
+            } => {
+                // Only a table which is assigned to something gets its `{` on a line of its own, see
+                // `value`
+                let style = BraceStyle::KAndR;
+                self.table_like(brace_open, entries, brace_close, Self::table_entry, style)
+            }
+            Expr::Function {
+                function_token,
+                body,
+            } => docs![token(function_token), self.function_body(body)],
+            Expr::Lambda { at, params, body } => {
+                docs![token(at), self.params(params), " ", self.expr(body)]
+            }
+            Expr::Class { class_token, body } => docs![token(class_token), self.class_body(body)],
+            Expr::Delegate {
+                delegate_token,
+                parent,
+                colon,
+                expr,
+            } => docs![
+                token(delegate_token),
+                " ",
+                self.expr(parent),
+                " ",
+                token(colon),
+                " ",
+                self.expr(expr)
+            ],
+
+            Expr::Unary { op, expr } => {
+                let separated = match op.kind {
+                    TokenKind::Typeof
+                    | TokenKind::Clone
+                    | TokenKind::Resume
+                    | TokenKind::Delete => true,
+                    // `- -a` and `- --a` mustn't become `--a` and `---a`
+                    TokenKind::Minus => {
+                        matches!(first_token(&**expr).kind, TokenKind::Minus | TokenKind::Dec)
+                    }
+                    _ => false,
+                };
+                docs![token(op), if separated { " " } else { "" }, self.expr(expr)]
+            }
+            Expr::Postfix { expr, op } => docs![self.expr(expr), token(op)],
+
+            Expr::Binary { lhs, op, rhs } if op.kind == TokenKind::Comma => {
+                docs![self.expr(lhs), token(op), " ", self.expr(rhs)]
+            }
+            Expr::Binary { lhs, op, rhs } if is_assignment(&op.kind) => {
+                docs![self.expr(lhs), " ", token(op), " ", self.value(rhs)]
+            }
+            Expr::Binary { lhs, op, rhs } => self.binary(lhs, op, rhs, true),
+
+            Expr::Ternary {
+                cond,
+                question,
+                then_branch,
+                colon,
+                else_branch,
+            } => group(docs![
+                self.expr(cond),
+                " ",
+                token(question),
+                indent(docs![
+                    Doc::Line,
+                    self.expr(then_branch),
+                    " ",
+                    token(colon),
+                    Doc::Line,
+                    self.expr(else_branch)
+                ])
+            ]),
+
+            Expr::Field { expr, dot, name } => {
+                // `1 .tofloat()` mustn't become `1.tofloat()`, which lexes as a float
+                let numeric = matches!(
+                    &**expr,
+                    Expr::Atom(TokenReference {
+                        token: Token {
+                            kind: TokenKind::Integer { .. } | TokenKind::Float { .. },
+                            ..
+                        },
+                        ..
+                    })
+                );
+                docs![
+                    self.expr(expr),
+                    if numeric { " " } else { "" },
+                    token(dot),
+                    token(name)
+                ]
+            }
+            Expr::Index {
+                expr,
+                square_open,
+                index,
+                square_close,
+            } => docs![
+                self.expr(expr),
+                token(square_open),
+                self.expr(index),
+                token(square_close)
+            ],
+            Expr::Call {
+                expr,
+                paren_open,
+                args,
+                paren_close,
+            } => docs![
+                self.expr(expr),
+                self.arguments(paren_open, args, paren_close)
+            ],
+        }
+    }
+}
+
+// The token with its leading and trailing comments.
+fn token(token: &TokenReference<'_>) -> Doc {
+    let comments = comments(&token.leading_trivia)
+        .into_iter()
+        .flat_map(|(comment, separator)| [comment, separator]);
+    let mut docs: Vec<Doc> = comments.collect();
+    docs.push(bare_token(token));
+    Doc::Concat(docs)
+}
+
+// The token with its trailing comments, for tokens whose leading comments were already lowered by
+// `dangling_comments`.
+fn bare_token(token: &TokenReference<'_>) -> Doc {
+    let mut docs = vec![Doc::Text(token.kind.to_string())];
+    for trivia in &token.trailing_trivia {
+        match &trivia.kind {
+            TokenKind::Comment(text) | TokenKind::DocComment(text) => {
+                docs.push(Doc::LineSuffix(format!(" {text}")))
+            }
+            TokenKind::MultiLineComment(text) => {
+                docs.extend([" ".into(), multi_line_comment(text)])
+            }
+            TokenKind::DocBlockComment(text) => docs.extend([" ".into(), doc_block_comment(text)]),
+            _ => {}
+        }
+    }
+    Doc::Concat(docs)
+}
+
+fn optional_token(token: &Option<TokenReference<'_>>) -> Doc {
+    token.as_ref().map_or_else(nil, self::token)
+}
+
+// Every comment in `trivia`, along with what should follow it: a space if the next comment or token
+// was on the same line, otherwise a line break, doubled if there was a blank line.
+fn comments(trivia: &[Token<'_>]) -> Vec<(Doc, Doc)> {
+    let mut comments = Vec::new();
+    for (i, comment) in trivia.iter().enumerate() {
+        let (TokenKind::Comment(text)
+        | TokenKind::MultiLineComment(text)
+        | TokenKind::DocComment(text)
+        | TokenKind::DocBlockComment(text)) = &comment.kind
+        else {
+            continue;
+        };
+        let newlines = trivia[i + 1..]
+            .iter()
+            .take_while(|trivia| {
+                matches!(
+                    trivia.kind,
+                    TokenKind::Whitespace(_) | TokenKind::Newline(_)
+                )
+            })
+            .filter(|trivia| matches!(trivia.kind, TokenKind::Newline(_)))
+            .count();
+        let separator = match newlines {
+            0 if matches!(
+                comment.kind,
+                TokenKind::MultiLineComment(_) | TokenKind::DocBlockComment(_)
+            ) =>
+            {
+                Doc::from(" ")
+            }
+            0 | 1 => docs![Doc::BreakParent, Doc::HardLine],
+            _ => docs![Doc::BreakParent, Doc::HardLine, Doc::HardLine],
+        };
+        let comment = match comment.kind {
+            TokenKind::MultiLineComment(_) => multi_line_comment(text),
+            TokenKind::DocBlockComment(_) => doc_block_comment(text),
+            _ => (&**text).into(),
+        };
+        comments.push((comment, separator));
+    }
+    comments
+}
+
+// The lines of a multi-line comment are kept as they are, but they get the configured line ending.
+fn multi_line_comment(text: &str) -> Doc {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut docs = Vec::new();
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            docs.push(Doc::LiteralLine);
+        }
+        docs.push(line.into());
+    }
+    Doc::Concat(docs)
+}
+
+// A doc comment with a ` * ` gutter, i.e. whose lines after the first all start with a `*`, is
+// re-indented so that the `*`s line up under the first one of its `/**`. Other doc comments are kept
+// as they are, like any multi-line comment.
+fn doc_block_comment(text: &str) -> Doc {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let (first, rest) = text.split_once('\n').unwrap_or((&text, ""));
+    let lines: Vec<_> = rest.split('\n').map(str::trim_start).collect();
+    if rest.is_empty() || !lines.iter().all(|line| line.starts_with('*')) {
+        return multi_line_comment(&text);
+    }
+    let mut docs = vec![first.into()];
+    for line in lines {
+        docs.extend([Doc::HardLine, " ".into(), line.into()]);
+    }
+    Doc::Concat(docs)
+}
+
+// The comments before a closing token like `}`, which go on the lines before it, without anything
+// after the last of them.
+fn dangling_comments(token: &TokenReference<'_>) -> Option<Doc> {
+    let mut comments = comments(&token.leading_trivia);
+    let (last, _) = comments.pop()?;
+    let mut docs: Vec<Doc> = comments
+        .into_iter()
+        .flat_map(|(comment, separator)| [comment, separator])
+        .collect();
+    docs.push(last);
+    Some(Doc::Concat(docs))
+}
+
+// Whether the source had a blank line before the token (or the comments above it).
+fn blank_line_before(token: &TokenReference<'_>) -> bool {
+    token
+        .leading_trivia
+        .iter()
+        .find(|trivia| !matches!(trivia.kind, TokenKind::Whitespace(_)))
+        .is_some_and(|trivia| matches!(trivia.kind, TokenKind::Newline(_)))
+}
+
+fn first_token(node: &impl Node) -> &TokenReference<'_> {
+    node.first_token().expect("nodes have at least one token")
+}
+
+// The lines taken up by statements, including the comments before them and after them on their last
+// line, as an exclusive range.
+fn stmt_lines(stmts: &[Stmt<'_>]) -> Range<u32> {
+    let mut tokens = Vec::new();
+    for stmt in stmts {
+        stmt.tokens(&mut tokens);
+    }
+    let (first, last) = (tokens[0], tokens[tokens.len() - 1]);
+    let start = first
+        .leading_trivia
+        .iter()
+        .find(|trivia| is_comment(trivia))
+        .map_or(first.start_line, |comment| comment.start_line);
+    let end = last
+        .trailing_trivia
+        .iter()
+        .filter(|trivia| is_comment(trivia))
+        .map(|comment| comment.end_line)
+        .fold(last.end_line, u32::max);
+    start..end + 1
+}
+
+fn is_comment(trivia: &Token<'_>) -> bool {
+    matches!(
+        trivia.kind,
+        TokenKind::Comment(_)
+            | TokenKind::MultiLineComment(_)
+            | TokenKind::DocComment(_)
+            | TokenKind::DocBlockComment(_)
+    )
+}
+
+// Whether nothing comes before the statements on their first line
+fn starts_line(stmts: &[Stmt<'_>]) -> bool {
+    let first = first_token(&stmts[0]);
+    first
+        .leading_trivia
+        .first()
+        .map_or(first.start_column, |trivia| trivia.start_column)
+        == 1
+}
+
+// Whether nothing but comments come after the statements on their last line
+fn ends_line(stmts: &[Stmt<'_>]) -> bool {
+    let mut tokens = Vec::new();
+    stmts[stmts.len() - 1].tokens(&mut tokens);
+    tokens[tokens.len() - 1]
+        .trailing_trivia
+        .last()
+        .is_some_and(|trivia| matches!(trivia.kind, TokenKind::Newline(_)))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Directive {
+    Off,
+    On,
+    Skip,
+}
+
+// The `squirrelfmt:` directives in the comments before a token. Only comments on lines of their own
+// count, since the ones after a token on the same line belong to the token before.
+fn directives(token: &TokenReference<'_>) -> impl Iterator<Item = Directive> {
+    token.leading_trivia.iter().filter_map(|trivia| {
+        let text = match &trivia.kind {
+            TokenKind::Comment(text) => text.strip_prefix("//").or(text.strip_prefix('#'))?,
+            TokenKind::MultiLineComment(text) => text.strip_prefix("/*")?.strip_suffix("*/")?,
+            _ => return None,
+        };
+        match text.trim() {
+            "squirrelfmt: off" => Some(Directive::Off),
+            "squirrelfmt: on" => Some(Directive::On),
+            "squirrelfmt: skip" => Some(Directive::Skip),
+            _ => None,
+        }
+    })
+}
+
+// Statements exactly as they are in the source, from their first token up to the end of the last
+// line, after the comments before them. Only the first line is indented.
+fn verbatim(stmts: &[Stmt<'_>]) -> Doc {
+    let mut tokens = Vec::new();
+    for stmt in stmts {
+        stmt.tokens(&mut tokens);
+    }
+    let (first, rest) = tokens
+        .split_first()
+        .expect("statements have at least one token");
+
+    let mut text = first.kind.to_string();
+    for trivia in &first.trailing_trivia {
+        text.push_str(&trivia.kind.to_string());
+    }
+    for token in rest {
+        text.push_str(&token.to_string());
+    }
+
+    let comments = comments(&first.leading_trivia)
+        .into_iter()
+        .flat_map(|(comment, separator)| [comment, separator]);
+    let mut docs: Vec<Doc> = comments.collect();
+    docs.push(Doc::Text(text.trim_end().to_owned()));
+    Doc::Concat(docs)
+}
+
+// Puts each item on its own line, keeping a blank line between two items wherever the source had
+// some.
+fn lines<'a>(items: impl IntoIterator<Item = (&'a TokenReference<'a>, Doc)>) -> Doc {
+    let mut docs = Vec::new();
+    for (i, (first_token, doc)) in items.into_iter().enumerate() {
+        if i > 0 {
+            docs.push(Doc::HardLine);
+            if blank_line_before(first_token) {
+                docs.push(Doc::HardLine);
+            }
+        }
+        docs.push(doc);
+    }
+    Doc::Concat(docs)
+}
+
+// What goes between a header like `if (a)` and the `{` after it. Empty braces without comments stay
+// on the line of the header whatever the brace style.
+fn before_brace(style: BraceStyle, empty: bool, brace_close: &TokenReference<'_>) -> Doc {
+    let empty = empty && dangling_comments(brace_close).is_none();
+    match style {
+        BraceStyle::Allman if !empty => Doc::HardLine,
+        _ => " ".into(),
+    }
+}
+
+fn is_block(stmt: &Stmt<'_>) -> bool {
+    matches!(stmt.kind, StmtKind::Block(_))
+}
+
+// Whether `node` would be read as part of the item before it if it followed it on the same line
+// without a comma. For example `[a ++b]` is an increment rather than two items. A `[` can't start
+// such an item at all, since it would be an error on a line of its own.
+fn continues_previous_item(node: &impl Node) -> bool {
+    matches!(first_token(node).kind, TokenKind::Inc | TokenKind::Dec)
+}
+
+// Whether an argument can be laid out across several lines while the rest of the call stays on
+// one, e.g. a function or a table as the last argument.
+fn is_huggable(arg: &Expr<'_>) -> bool {
+    matches!(
+        arg,
+        Expr::Function { .. }
+            | Expr::Lambda { .. }
+            | Expr::Class { .. }
+            | Expr::Table { .. }
+            | Expr::Array { .. }
+    )
+}
+
+fn is_assignment(op: &TokenKind<'_>) -> bool {
+    matches!(
+        op,
+        TokenKind::Eq
+            | TokenKind::Ins
+            | TokenKind::PlusEq
+            | TokenKind::MinusEq
+            | TokenKind::MultEq
+            | TokenKind::DivEq
+            | TokenKind::ModEq
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BraceStyles, Dialect};
+    use crate::lexer::{Lexer, LexerError};
+    use crate::parser::Parser;
+
+    fn format_with_config(source: &str, config: &Config) -> String {
+        let tokens = Lexer::new(source)
+            .with_dialect(config.dialect)
+            .collect::<Result<Vec<Token>, LexerError>>()
+            .unwrap();
+        let chunk = Parser::new(tokens).parse().unwrap();
+        let output = format(&chunk, config);
+        // Every expected output is also checked to be stable
+        if let Err(error) = crate::verify::check_idempotent(&output, config) {
+            panic!("{error}");
+        }
+        output
+    }
+
+    fn format_with_width(source: &str, max_width: usize) -> String {
+        let config = Config {
+            max_width,
+            ..Config::default()
+        };
+        format_with_config(source, &config)
+    }
+
+    macro_rules! assert_format {
+        ($source: expr, $expected: expr) => {
+            assert_format!($source, $expected, 100)
+        };
+        ($source: expr, $expected: expr, $width: expr) => {
+            assert_eq!(format_with_width($source, $width), $expected)
+        };
+    }
+
+    #[test]
+    fn empty() {
+        assert_format!("", "");
+        assert_format!("\n\n  \n", "");
+    }
+
+    #[test]
+    fn spacing() {
+        assert_format!("local   a=1 ,b", "local a = 1, b\n");
+        assert_format!("x<-a+b*c", "x <- a + b * c\n");
+        assert_format!(
+            "if(a){b()}else if(c)d();else{}",
+            "if (a) {\n    b()\n} else if (c)\n    d();\nelse {}\n"
+        );
+        assert_format!("foreach(k ,v in t){}", "foreach (k, v in t) {}\n");
+        assert_format!(
+            "for(local i=0;i<10;i++);",
+            "for (local i = 0; i < 10; i++);\n"
+        );
+        assert_format!("for(;;){}", "for (;;) {}\n");
+        assert_format!("a = typeof(b)", "a = typeof (b)\n");
+        assert_format!("::print( a.b[ c ]( ) ++ )", "::print(a.b[c]()++)\n");
+    }
+
+    #[test]
+    fn statements_on_own_lines() {
+        assert_format!("a(); b()", "a();\nb()\n");
+        assert_format!("do{i--}while(i)", "do {\n    i--\n} while (i)\n");
+        assert_format!(
+            "try{throw 1}catch(e){}",
+            "try {\n    throw 1\n} catch (e) {}\n"
+        );
+        assert_format!(
+            "switch(x){case 1:case 2:a();break\ndefault:{b()}}",
+            "switch (x) {\n    case 1:\n    case 2:\n        a();\n        break\n    default: {\n        b()\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn blank_lines() {
+        assert_format!("\n\na()\n\n\n\nb()\nc()\n\n", "a()\n\nb()\nc()\n");
+        assert_format!("{\n\n  a()\n\n}", "{\n    a()\n}\n");
+    }
+
+    #[test]
+    fn comments() {
+        assert_format!("a() // trailing\nb()", "a() // trailing\nb()\n");
+        assert_format!("// one\n\n// two\na()", "// one\n\n// two\na()\n");
+        assert_format!("/* inline */ a()", "/* inline */ a()\n");
+        assert_format!("{\n  a()\n\n  // last\n}", "{\n    a()\n\n    // last\n}\n");
+        assert_format!("a()\n// end", "a()\n// end\n");
+        assert_format!("foo(a, // why\nb)", "foo(\n    a, // why\n    b\n)\n");
+    }
+
+    #[test]
+    fn doc_comments() {
+        assert_format!("a() /// doc\nb()", "a() /// doc\nb()\n");
+        assert_format!(
+            "class A {\n/**\n   * Does b.\n   *\n   * @param c\n*/\nfunction b(c) {}\n}",
+            "class A {\n    /**\n     * Does b.\n     *\n     * @param c\n     */\n    function b(c) {}\n}\n"
+        );
+        // Without a gutter, the lines are kept as they are
+        assert_format!(
+            "{\n/** Does\n  b. */\nb()\n}",
+            "{\n    /** Does\n  b. */\n    b()\n}\n"
+        );
+        assert_format!("/** one */ a()", "/** one */ a()\n");
+    }
+
+    #[test]
+    fn line_endings() {
+        let format_with = |source, line_endings| {
+            let tokens = Lexer::new(source)
+                .collect::<Result<Vec<Token>, LexerError>>()
+                .unwrap();
+            let chunk = Parser::new(tokens).parse().unwrap();
+            let config = Config {
+                line_endings,
+                ..Config::default()
+            };
+            format(&chunk, &config)
+        };
+
+        let source = "a()\r\n/* x\r\ny */\nb = @\"1\r\n2\"";
+        assert_eq!(
+            format_with(source, LineEndings::Lf),
+            "a()\n/* x\ny */\nb = @\"1\r\n2\"\n"
+        );
+        assert_eq!(
+            format_with(source, LineEndings::CrLf),
+            "a()\r\n/* x\r\ny */\r\nb = @\"1\r\n2\"\r\n"
+        );
+        assert_eq!(
+            format_with(source, LineEndings::Auto),
+            format_with(source, LineEndings::CrLf)
+        );
+        assert_eq!(format_with("a()\rb()", LineEndings::Auto), "a()\rb()\r");
+        assert_eq!(format_with("a()", LineEndings::Auto), "a()\n");
+    }
+
+    #[test]
+    fn wrap_arguments() {
+        assert_format!("foo(aaaa, bbbb)", "foo(aaaa, bbbb)\n", 15);
+        assert_format!("foo(aaaa, bbbb)", "foo(\n    aaaa,\n    bbbb\n)\n", 14);
+        assert_format!("foo(aaaa, bbbb) // c", "foo(aaaa, bbbb) // c\n", 15);
+    }
+
+    #[test]
+    fn hug_last_argument() {
+        assert_format!(
+            "on(\"x\", function(a) { print(a) })",
+            "on(\"x\", function(a) {\n    print(a)\n})\n"
+        );
+        assert_format!(
+            "on(\"x\", function(a) { print(a) })",
+            "on(\n    \"x\",\n    function(a) {\n        print(a)\n    }\n)\n",
+            20
+        );
+    }
+
+    #[test]
+    fn wrap_tables() {
+        assert_format!("t = {a = 1, b = 2}", "t = { a = 1, b = 2 }\n");
+        assert_format!(
+            "t = {a = 1, b = 2}",
+            "t = {\n    a = 1,\n    b = 2\n}\n",
+            15
+        );
+        assert_format!("t = {\na = 1 b = 2}", "t = {\n    a = 1\n    b = 2\n}\n");
+        assert_format!("t = {}", "t = {}\n");
+        assert_format!("enum E {A, B}", "enum E { A, B }\n");
+    }
+
+    #[test]
+    fn wrap_binary_operators() {
+        assert_format!(
+            "x = aaaa + bbbb * cccc - dddd",
+            "x = aaaa +\n    bbbb * cccc -\n    dddd\n",
+            20
+        );
+        assert_format!(
+            "if (aaaa && bbbb || cccc) {}",
+            "if (\n    aaaa && bbbb ||\n    cccc\n) {}\n",
+            20
+        );
+        assert_format!(
+            "x = aaaa ? bbbb : cccc",
+            "x = aaaa ?\n    bbbb :\n    cccc\n",
+            15
+        );
+    }
+
+    #[test]
+    fn if_chains() {
+        assert_format!(
+            "if (a) b()\nelse if (c) d()\nelse e()",
+            "if (a)\n    b()\nelse if (c)\n    d()\nelse\n    e()\n"
+        );
+        assert_format!(
+            "if (a) {} else if (b) {} else {}",
+            "if (a) {} else if (b) {} else {}\n"
+        );
+    }
+
+    #[test]
+    fn classes() {
+        assert_format!(
+            "class A extends B </ x = 1 /> { </ y = 2 /> static z = 1; constructor() {} }",
+            "class A extends B </ x = 1 /> {\n    </ y = 2 />\n    static z = 1;\n    constructor() {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn newline_sensitive_tokens() {
+        // Without a comma, `[a ++b]` would be an increment
+        assert_format!("x = [a\n++b]", "x = [\n    a\n    ++b\n]\n");
+        assert_format!("x = - -a - --b", "x = - -a - --b\n");
+        assert_format!("x = 1 .tostring()", "x = 1 .tostring()\n");
+    }
+
+    #[test]
+    fn allman() {
+        let config = Config {
+            brace_style: BraceStyles::all(BraceStyle::Allman),
+            ..Config::default()
+        };
+        let allman = |source| format_with_config(source, &config);
+        assert_eq!(
+            allman("if (a) { b() } else if (c) { d() } else {}"),
+            "if (a)\n{\n    b()\n}\nelse if (c)\n{\n    d()\n}\nelse {}\n"
+        );
+        assert_eq!(
+            allman("function f(a) { return a }"),
+            "function f(a)\n{\n    return a\n}\n"
+        );
+        assert_eq!(
+            allman("class A extends B { x = 1 }"),
+            "class A extends B\n{\n    x = 1\n}\n"
+        );
+        assert_eq!(
+            allman("do { i-- } while (i)"),
+            "do\n{\n    i--\n}\nwhile (i)\n"
+        );
+        assert_eq!(
+            allman("switch (x) { default: { a() } }"),
+            "switch (x)\n{\n    default:\n    {\n        a()\n    }\n}\n"
+        );
+        // Tables and enums only get a line of their own when they are expanded
+        assert_eq!(allman("local t = { a = 1 }"), "local t = { a = 1 }\n");
+        assert_eq!(
+            allman("local t = {\na = 1 }\nenum E {\nA }"),
+            "local t =\n{\n    a = 1\n}\nenum E\n{\n    A\n}\n"
+        );
+        assert_eq!(allman("foo({\na = 1 })"), "foo({\n    a = 1\n})\n");
+    }
+
+    #[test]
+    fn mixed_brace_styles() {
+        let config = Config {
+            brace_style: BraceStyles {
+                declarations: BraceStyle::Allman,
+                control_flow: BraceStyle::KAndR,
+                tables: BraceStyle::KAndR,
+            },
+            ..Config::default()
+        };
+        assert_eq!(
+            format_with_config(
+                "function f() { if (a) { b() } }\nlocal t = {\nc = function() { d() } }",
+                &config
+            ),
+            "function f()\n{\n    if (a) {\n        b()\n    }\n}\nlocal t = {\n    c = function()\n    {\n        d()\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn directives() {
+        assert_format!(
+            "a=1\n// squirrelfmt: off\nb   =  [1,\n  2]\n\nc  = 3 // three\n// squirrelfmt: on\nd=4",
+            "a = 1\n// squirrelfmt: off\nb   =  [1,\n  2]\n\nc  = 3 // three\n// squirrelfmt: on\nd = 4\n"
+        );
+        assert_format!(
+            "# squirrelfmt: skip\nlocal t = {a=1,  bb=2}\nx=1",
+            "# squirrelfmt: skip\nlocal t = {a=1,  bb=2}\nx = 1\n"
+        );
+        assert_format!(
+            "function f() {\n  /* squirrelfmt: off */\n  x  =  1\n    y=2 }",
+            "function f() {\n    /* squirrelfmt: off */\n    x  =  1\n    y=2\n}\n"
+        );
+        // Only comments on lines of their own are directives
+        assert_format!(
+            "a=1 // squirrelfmt: skip\nb=2",
+            "a = 1 // squirrelfmt: skip\nb = 2\n"
+        );
+    }
+
+    fn format_lines(source: &str, lines: RangeInclusive<u32>) -> String {
+        let tokens = Lexer::new(source)
+            .collect::<Result<Vec<Token>, LexerError>>()
+            .unwrap();
+        let chunk = Parser::new(tokens).parse().unwrap();
+        format_range(&chunk, &Config::default(), lines)
+    }
+
+    #[test]
+    fn range() {
+        let source = "a=1
+b  =2 // two
+
+
+// c
+c=[1,
+2]
+d=4";
+        assert_eq!(
+            format_lines(source, 2..=2),
+            "a=1\nb = 2 // two\n\n\n// c\nc=[1,\n2]\nd=4"
+        );
+        assert_eq!(
+            format_lines(source, 3..=6),
+            "a=1\nb  =2 // two\n\n\n// c\nc = [1, 2]\nd=4"
+        );
+        assert_eq!(
+            format_lines(source, 8..=8),
+            "a=1\nb  =2 // two\n\n\n// c\nc=[1,\n2]\nd = 4"
+        );
+        // Blank lines between statements
+        assert_eq!(format_lines(source, 3..=4), source);
+        // Statements sharing a line are formatted together
+        assert_eq!(
+            format_lines("a=1;b=2\nc=3;d=4", 2..=2),
+            "a=1;b=2\nc = 3;\nd = 4"
+        );
+    }
+
+    #[test]
+    fn nested_range() {
+        let source = "function f(){\n  if(a){\n     x=1\n  y=2\n  }\n}\nz=3";
+        assert_eq!(
+            format_lines(source, 3..=3),
+            "function f(){\n  if(a){\n        x = 1\n  y=2\n  }\n}\nz=3"
+        );
+        // The header of the `if` selects the whole `if`
+        assert_eq!(
+            format_lines(source, 2..=3),
+            "function f(){\n    if (a) {\n        x = 1\n        y = 2\n    }\n}\nz=3"
+        );
+        // Statements on the same line as the braces select the enclosing statement
+        assert_eq!(
+            format_lines("x=1\nif(a){ b=1\nc=2 }", 3..=3),
+            "x=1\nif (a) {\n    b = 1\n    c = 2\n}"
+        );
+    }
+
+    #[test]
+    fn squirrel2() {
+        let config = Config::builder().dialect(Dialect::Squirrel2).build();
+        assert_eq!(
+            format_with_config("local t=delegate parent:{a=vargv[vargc-1]}", &config),
+            "local t = delegate parent : { a = vargv[vargc - 1] }\n"
+        );
+    }
+}
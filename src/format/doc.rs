@@ -0,0 +1,450 @@
+//! A Wadler-style document IR and the printer that lays it out.
+//!
+//! The design follows Prettier's: a group is printed on one line if it fits in the remaining width,
+//! otherwise its line breaks are taken. Hard line breaks (and anything that requires one, such as a
+//! line comment) force every group around them to break.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::config::Config;
+use crate::lexer::LineEnding;
+
+#[derive(Debug)]
+pub enum Doc {
+    Text(String),
+    /// A space if the enclosing group fits on one line, a line break otherwise.
+    Line,
+    /// Nothing if the enclosing group fits on one line, a line break otherwise.
+    SoftLine,
+    /// Always a line break.
+    HardLine,
+    /// A line break without indentation, for the lines inside a multi-line comment.
+    LiteralLine,
+    /// Forces the enclosing groups to break without printing anything.
+    BreakParent,
+    /// Text that is held back until the next line break, used for trailing line comments.
+    LineSuffix(String),
+    /// Prints nothing, but records where in the output it ended up. See [`print_marked`].
+    Mark,
+    Concat(Vec<Doc>),
+    Indent(Box<Doc>),
+    /// Indents the doc only if the enclosing group breaks. Used for the items of a list, so that the
+    /// last item of a hugged list isn't indented.
+    IndentIfBreak(Box<Doc>),
+    Group {
+        doc: Box<Doc>,
+        /// Whether the group must break. Set for groups which contain a hard line break.
+        broken: bool,
+    },
+    /// A group which is not forced to break by the hard line breaks inside it: it is printed flat
+    /// whenever everything up to its first line break fits. This is what lets a call like
+    /// `foo(function() { ... })` keep the function on its first line.
+    Hug(Box<Doc>),
+}
+
+impl From<&str> for Doc {
+    fn from(text: &str) -> Self {
+        Doc::Text(text.into())
+    }
+}
+
+impl From<String> for Doc {
+    fn from(text: String) -> Self {
+        Doc::Text(text)
+    }
+}
+
+/// Builds a `Doc::Concat` out of anything that converts into a `Doc`.
+macro_rules! docs {
+    ($($doc: expr),* $(,)?) => {
+        $crate::format::doc::Doc::Concat(vec![$($crate::format::doc::Doc::from($doc)),*])
+    };
+}
+
+pub(crate) use docs;
+
+pub fn nil() -> Doc {
+    Doc::Concat(Vec::new())
+}
+
+pub fn indent(doc: impl Into<Doc>) -> Doc {
+    Doc::Indent(Box::new(doc.into()))
+}
+
+pub fn indent_if_break(doc: impl Into<Doc>) -> Doc {
+    Doc::IndentIfBreak(Box::new(doc.into()))
+}
+
+pub fn group(doc: impl Into<Doc>) -> Doc {
+    Doc::Group {
+        doc: Box::new(doc.into()),
+        broken: false,
+    }
+}
+
+pub fn hug(doc: impl Into<Doc>) -> Doc {
+    Doc::Hug(Box::new(doc.into()))
+}
+
+/// A group which always breaks, regardless of its width.
+pub fn broken_group(doc: impl Into<Doc>) -> Doc {
+    Doc::Group {
+        doc: Box::new(doc.into()),
+        broken: true,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Lays out `doc` within `config.max_width` columns, ending lines with `line_ending`.
+pub fn print(doc: Doc, config: &Config, line_ending: LineEnding) -> String {
+    print_marked(doc, config, line_ending).0
+}
+
+/// Like [`print`], but also returns the byte offset into the output of every [`Doc::Mark`], in the
+/// order they were printed.
+pub fn print_marked(
+    mut doc: Doc,
+    config: &Config,
+    line_ending: LineEnding,
+) -> (String, Vec<usize>) {
+    propagate_breaks(&mut doc);
+
+    let mut printer = Printer {
+        config,
+        line_ending: line_ending.as_str(),
+        output: String::new(),
+        column: 0,
+        line_suffixes: Vec::new(),
+        remeasure: false,
+        marks: Vec::new(),
+    };
+    let mut commands = vec![(0, Mode::Break, &doc)];
+
+    while let Some((level, mode, doc)) = commands.pop() {
+        match doc {
+            Doc::Text(text) => printer.text(text),
+
+            Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                if let Doc::Line = doc {
+                    printer.text(" ");
+                }
+            }
+
+            Doc::Line | Doc::SoftLine | Doc::HardLine => printer.newline(level),
+            Doc::LiteralLine => printer.newline(0),
+            Doc::BreakParent => {}
+            Doc::LineSuffix(text) => printer.line_suffixes.push(text),
+            Doc::Mark => printer.marks.push(printer.output.len()),
+
+            Doc::Concat(docs) => {
+                commands.extend(docs.iter().rev().map(|doc| (level, mode, doc)));
+            }
+
+            Doc::Indent(doc) => commands.push((level + 1, mode, doc)),
+            Doc::IndentIfBreak(doc) => match mode {
+                Mode::Flat => commands.push((level, mode, doc)),
+                Mode::Break => commands.push((level + 1, mode, doc)),
+            },
+
+            Doc::Group { doc, broken: true } => commands.push((level, Mode::Break, doc)),
+
+            // Groups inside a flat group are flat too, unless a hard line break was printed since,
+            // in which case the rest of the group is on another line and has to be measured again
+            Doc::Group { doc, .. } | Doc::Hug(doc) => {
+                let mode = if (mode == Mode::Flat && !printer.remeasure)
+                    || printer.fits(&commands, (Mode::Flat, doc))
+                {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                printer.remeasure = false;
+                commands.push((level, mode, doc));
+            }
+        }
+    }
+
+    printer.flush_line_suffixes();
+    (printer.output, printer.marks)
+}
+
+// Marks every group containing a hard line break (or a `BreakParent`) as broken, and returns whether
+// `doc` contains one. Hugging groups are not marked and don't propagate their breaks further.
+fn propagate_breaks(doc: &mut Doc) -> bool {
+    match doc {
+        Doc::Text(_) | Doc::Line | Doc::SoftLine | Doc::LineSuffix(_) | Doc::Mark => false,
+        Doc::HardLine | Doc::LiteralLine | Doc::BreakParent => true,
+        Doc::Concat(docs) => {
+            // Every doc has to be visited, so this can't short-circuit
+            let mut breaks = false;
+            for doc in docs {
+                breaks |= propagate_breaks(doc);
+            }
+            breaks
+        }
+        Doc::Indent(doc) | Doc::IndentIfBreak(doc) => propagate_breaks(doc),
+        Doc::Group { doc, broken } => {
+            *broken |= propagate_breaks(doc);
+            *broken
+        }
+        Doc::Hug(doc) => {
+            propagate_breaks(doc);
+            false
+        }
+    }
+}
+
+struct Printer<'a> {
+    config: &'a Config,
+    line_ending: &'static str,
+    output: String,
+    column: usize,
+    line_suffixes: Vec<&'a str>,
+    // Set when a line break is printed, see the handling of groups in `print`
+    remeasure: bool,
+    marks: Vec<usize>,
+}
+
+impl<'a> Printer<'a> {
+    fn text(&mut self, text: &str) {
+        match text.rsplit_once('\n') {
+            Some((_, last_line)) => self.column = width(last_line),
+            None => self.column += width(text),
+        }
+        self.output.push_str(text);
+    }
+
+    fn newline(&mut self, level: usize) {
+        self.flush_line_suffixes();
+        let trimmed = self.output.trim_end_matches([' ', '\t']).len();
+        self.output.truncate(trimmed);
+        self.output.push_str(self.line_ending);
+        self.remeasure = true;
+
+        self.column = level * self.config.indent_width;
+        if self.config.use_tabs {
+            self.output.extend(std::iter::repeat_n('\t', level));
+        } else {
+            self.output.extend(std::iter::repeat_n(' ', self.column));
+        }
+    }
+
+    fn flush_line_suffixes(&mut self) {
+        for suffix in std::mem::take(&mut self.line_suffixes) {
+            self.output.push_str(suffix);
+        }
+    }
+
+    // Whether `next` fits on the rest of the current line. The commands after it are checked too, up
+    // to the first line break, since they also end up on the same line if `next` is printed flat.
+    fn fits(&self, rest: &[(usize, Mode, &Doc)], next: (Mode, &Doc)) -> bool {
+        let mut remaining = self.config.max_width as isize - self.column as isize;
+        let mut has_line_suffix = !self.line_suffixes.is_empty();
+        let mut rest = rest.iter().rev().map(|&(_, mode, doc)| (mode, doc));
+        let mut commands = vec![next];
+
+        while remaining >= 0 {
+            let Some((mode, doc)) = commands.pop().or_else(|| rest.next()) else {
+                return true;
+            };
+
+            match doc {
+                Doc::Text(text) => match text.split_once('\n') {
+                    Some((first_line, _)) => return remaining >= width(first_line) as isize,
+                    None => remaining -= width(text) as isize,
+                },
+
+                Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                    // A pending line comment would end up in the middle of the line
+                    if has_line_suffix {
+                        return false;
+                    }
+                    if let Doc::Line = doc {
+                        remaining -= 1;
+                    }
+                }
+
+                Doc::Line | Doc::SoftLine | Doc::HardLine | Doc::LiteralLine => return true,
+                Doc::BreakParent => {
+                    if mode == Mode::Flat {
+                        return false;
+                    }
+                }
+                Doc::LineSuffix(_) => has_line_suffix = true,
+                Doc::Mark => {}
+                Doc::Concat(docs) => commands.extend(docs.iter().rev().map(|doc| (mode, doc))),
+                Doc::Indent(doc) | Doc::IndentIfBreak(doc) => commands.push((mode, doc)),
+                Doc::Group { doc, broken } => {
+                    commands.push((if *broken { Mode::Break } else { mode }, doc));
+                }
+                Doc::Hug(doc) => commands.push((mode, doc)),
+            }
+        }
+
+        false
+    }
+}
+
+fn width(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print_with_width(doc: Doc, max_width: usize) -> String {
+        let config = Config {
+            max_width,
+            ..Config::default()
+        };
+        print(doc, &config, LineEnding::Lf)
+    }
+
+    fn call(args: &[&str]) -> Doc {
+        let mut inner = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                inner.push(docs![",", Doc::Line]);
+            }
+            inner.push(Doc::from(*arg));
+        }
+        group(docs![
+            "f(",
+            indent(docs![Doc::SoftLine, Doc::Concat(inner)]),
+            Doc::SoftLine,
+            ")"
+        ])
+    }
+
+    #[test]
+    fn group_fits() {
+        assert_eq!(print_with_width(call(&["a", "b"]), 10), "f(a, b)");
+    }
+
+    #[test]
+    fn group_breaks() {
+        assert_eq!(
+            print_with_width(call(&["aaaa", "bbbb"]), 10),
+            "f(\n    aaaa,\n    bbbb\n)"
+        );
+    }
+
+    #[test]
+    fn hard_line_breaks_group() {
+        let doc = group(docs!["a", Doc::Line, "b", Doc::HardLine, "c"]);
+        assert_eq!(print_with_width(doc, 80), "a\nb\nc");
+    }
+
+    #[test]
+    fn line_suffix() {
+        let doc = docs![
+            "a",
+            Doc::LineSuffix(" // comment".into()),
+            ";",
+            Doc::HardLine,
+            "b"
+        ];
+        assert_eq!(print_with_width(doc, 80), "a; // comment\nb");
+    }
+
+    #[test]
+    fn line_suffix_breaks_group() {
+        let doc = group(docs!["a", Doc::LineSuffix(" // c".into()), Doc::Line, "b"]);
+        assert_eq!(print_with_width(doc, 80), "a // c\nb");
+    }
+
+    fn hugged_call(width: usize) -> String {
+        let body = docs![
+            "function() {",
+            indent(docs![Doc::HardLine, "body"]),
+            Doc::HardLine,
+            "}"
+        ];
+        let doc = hug(docs![
+            "f(",
+            indent_if_break(docs![Doc::SoftLine, "a,", Doc::Line, body]),
+            Doc::SoftLine,
+            ")"
+        ]);
+        print_with_width(doc, width)
+    }
+
+    #[test]
+    fn hug_fits() {
+        assert_eq!(hugged_call(80), "f(a, function() {\n    body\n})");
+    }
+
+    #[test]
+    fn hug_breaks() {
+        assert_eq!(
+            hugged_call(10),
+            "f(\n    a,\n    function() {\n        body\n    }\n)"
+        );
+    }
+
+    #[test]
+    fn remeasure_after_line_break() {
+        let doc = hug(docs![
+            "{",
+            indent(docs![Doc::HardLine, call(&["aaaa", "bbbb"])]),
+            Doc::HardLine,
+            "}"
+        ]);
+        assert_eq!(
+            print_with_width(doc, 10),
+            "{\n    f(\n        aaaa,\n        bbbb\n    )\n}"
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace_trimmed() {
+        let doc = docs!["a", indent(docs![Doc::HardLine, Doc::HardLine, "b"])];
+        assert_eq!(print_with_width(doc, 80), "a\n\n    b");
+    }
+
+    #[test]
+    fn marks() {
+        let doc = docs!["a", Doc::Mark, indent(docs![Doc::HardLine, "b", Doc::Mark])];
+        let (output, marks) = print_marked(doc, &Config::default(), LineEnding::Lf);
+        assert_eq!(output, "a\n    b");
+        assert_eq!(marks, vec![1, 7]);
+    }
+
+    #[test]
+    fn tabs() {
+        let config = Config {
+            use_tabs: true,
+            max_width: 10,
+            ..Config::default()
+        };
+        // The tab counts as four columns, so "bbbbb c" doesn't fit
+        let doc = docs![
+            "a",
+            indent(docs![Doc::HardLine, group(docs!["bbbbb", Doc::Line, "c"])])
+        ];
+        assert_eq!(print(doc, &config, LineEnding::Lf), "a\n\tbbbbb\n\tc");
+    }
+
+    #[test]
+    fn line_endings() {
+        let doc = docs![
+            "a",
+            indent(docs![Doc::HardLine, "/* b", Doc::LiteralLine, "c */"])
+        ];
+        assert_eq!(
+            print(doc, &Config::default(), LineEnding::CrLf),
+            "a\r\n    /* b\r\nc */"
+        );
+    }
+
+    #[test]
+    fn width_counts_graphemes() {
+        assert_eq!(print_with_width(call(&["ä", "ö"]), 7), "f(ä, ö)");
+    }
+}
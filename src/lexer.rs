@@ -1,14 +1,51 @@
+use std::borrow::Cow;
+use std::fmt;
+
 use derive_more::Constructor;
 use unicode_segmentation::UnicodeSegmentation;
 
-#[derive(Debug, PartialEq)]
-pub enum TokenKind {
-    Ident(String),
-    Lit(String),
-    Comment(String), // Includes both "//" and "#" comments
-    MultiLineComment(String),
-    Whitespace(String),
-    Newline,
+use crate::config::Dialect;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind<'src> {
+    Ident(Cow<'src, str>),
+    /// A decimal, octal (`072`) or hexadecimal (`0x1F`) integer.
+    Integer {
+        text: Cow<'src, str>,
+        value: i64,
+    },
+    /// A number with a fraction or an exponent, e.g. `3.1e5`.
+    Float {
+        text: Cow<'src, str>,
+        value: f64,
+    },
+    /// A character code, e.g. `'a'`, which is an integer in Squirrel.
+    Char {
+        text: Cow<'src, str>,
+        value: i64,
+    },
+    /// A string, e.g. `"a\tb"`. The value has its escape sequences decoded. Squirrel strings are
+    /// bytes, but the value is text, so `\xHH` gives the character U+00HH rather than the byte
+    /// 0xHH: `"\xFF"` is `"ÿ"`, which is two bytes in UTF-8.
+    String {
+        text: Cow<'src, str>,
+        value: Cow<'src, str>,
+    },
+    /// A string starting with `@`, e.g. `@"a ""b"""`. The value has each `""` turned into `"`.
+    VerbatimString {
+        text: Cow<'src, str>,
+        value: Cow<'src, str>,
+    },
+    Comment(Cow<'src, str>), // Includes both "//" and "#" comments
+    MultiLineComment(Cow<'src, str>),
+    /// A `///` comment, but not a `////` one.
+    DocComment(Cow<'src, str>),
+    /// A `/** */` comment, but neither `/**/` nor one starting with `/***`.
+    DocBlockComment(Cow<'src, str>),
+    Whitespace(Cow<'src, str>),
+    Error(Cow<'src, str>), // Bytes which couldn't be lexed, only produced by a recovering `Lexer`
+    Newline(LineEnding),
+    Eof, // Never produced by the lexer, the parser appends it to mark the end of the stream
 
     // Keywords
     Base,
@@ -21,7 +58,9 @@ pub enum TokenKind {
     Constructor,
     Continue,
     Default,
+    Delegate,
     Delete,
+    Do,
     Else,
     Enum,
     Extends,
@@ -36,6 +75,7 @@ pub enum TokenKind {
     Line,
     Local,
     Null,
+    Parent,
     Rawcall,
     Resume,
     Return,
@@ -46,6 +86,8 @@ pub enum TokenKind {
     True,
     Try,
     Typeof,
+    Vargc,
+    Vargv,
     While,
     Yield,
 
@@ -101,19 +143,336 @@ pub enum TokenKind {
     Semicolon,
     Scope,
     At,
+    AttrOpen,
+    AttrClose,
+}
+
+impl TokenKind<'_> {
+    /// Whether the token has no meaning to the parser, i.e. whitespace, newlines and comments.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace(_)
+                | TokenKind::Newline(_)
+                | TokenKind::Comment(_)
+                | TokenKind::MultiLineComment(_)
+                | TokenKind::DocComment(_)
+                | TokenKind::DocBlockComment(_)
+        )
+    }
+
+    /// Whether the token is a number, character code or string literal.
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Integer { .. }
+                | TokenKind::Float { .. }
+                | TokenKind::Char { .. }
+                | TokenKind::String { .. }
+                | TokenKind::VerbatimString { .. }
+        )
+    }
+
+    /// The token with its text copied, so that it can outlive the source it was lexed from.
+    pub fn into_owned(self) -> TokenKind<'static> {
+        let owned = |text: Cow<'_, str>| Cow::Owned(text.into_owned());
+        match self {
+            TokenKind::Ident(text) => TokenKind::Ident(owned(text)),
+            TokenKind::Integer { text, value } => TokenKind::Integer {
+                text: owned(text),
+                value,
+            },
+            TokenKind::Float { text, value } => TokenKind::Float {
+                text: owned(text),
+                value,
+            },
+            TokenKind::Char { text, value } => TokenKind::Char {
+                text: owned(text),
+                value,
+            },
+            TokenKind::String { text, value } => TokenKind::String {
+                text: owned(text),
+                value: owned(value),
+            },
+            TokenKind::VerbatimString { text, value } => TokenKind::VerbatimString {
+                text: owned(text),
+                value: owned(value),
+            },
+            TokenKind::Comment(text) => TokenKind::Comment(owned(text)),
+            TokenKind::MultiLineComment(text) => TokenKind::MultiLineComment(owned(text)),
+            TokenKind::DocComment(text) => TokenKind::DocComment(owned(text)),
+            TokenKind::DocBlockComment(text) => TokenKind::DocBlockComment(owned(text)),
+            TokenKind::Whitespace(text) => TokenKind::Whitespace(owned(text)),
+            TokenKind::Error(text) => TokenKind::Error(owned(text)),
+            TokenKind::Newline(ending) => TokenKind::Newline(ending),
+            TokenKind::Eof => TokenKind::Eof,
+            TokenKind::Base => TokenKind::Base,
+            TokenKind::Break => TokenKind::Break,
+            TokenKind::Case => TokenKind::Case,
+            TokenKind::Catch => TokenKind::Catch,
+            TokenKind::Class => TokenKind::Class,
+            TokenKind::Clone => TokenKind::Clone,
+            TokenKind::Const => TokenKind::Const,
+            TokenKind::Constructor => TokenKind::Constructor,
+            TokenKind::Continue => TokenKind::Continue,
+            TokenKind::Default => TokenKind::Default,
+            TokenKind::Delegate => TokenKind::Delegate,
+            TokenKind::Delete => TokenKind::Delete,
+            TokenKind::Do => TokenKind::Do,
+            TokenKind::Else => TokenKind::Else,
+            TokenKind::Enum => TokenKind::Enum,
+            TokenKind::Extends => TokenKind::Extends,
+            TokenKind::False => TokenKind::False,
+            TokenKind::File => TokenKind::File,
+            TokenKind::For => TokenKind::For,
+            TokenKind::Foreach => TokenKind::Foreach,
+            TokenKind::Function => TokenKind::Function,
+            TokenKind::If => TokenKind::If,
+            TokenKind::In => TokenKind::In,
+            TokenKind::Instanceof => TokenKind::Instanceof,
+            TokenKind::Line => TokenKind::Line,
+            TokenKind::Local => TokenKind::Local,
+            TokenKind::Null => TokenKind::Null,
+            TokenKind::Parent => TokenKind::Parent,
+            TokenKind::Rawcall => TokenKind::Rawcall,
+            TokenKind::Resume => TokenKind::Resume,
+            TokenKind::Return => TokenKind::Return,
+            TokenKind::Static => TokenKind::Static,
+            TokenKind::Switch => TokenKind::Switch,
+            TokenKind::This => TokenKind::This,
+            TokenKind::Throw => TokenKind::Throw,
+            TokenKind::True => TokenKind::True,
+            TokenKind::Try => TokenKind::Try,
+            TokenKind::Typeof => TokenKind::Typeof,
+            TokenKind::Vargc => TokenKind::Vargc,
+            TokenKind::Vargv => TokenKind::Vargv,
+            TokenKind::While => TokenKind::While,
+            TokenKind::Yield => TokenKind::Yield,
+            TokenKind::Plus => TokenKind::Plus,
+            TokenKind::PlusEq => TokenKind::PlusEq,
+            TokenKind::Inc => TokenKind::Inc,
+            TokenKind::Minus => TokenKind::Minus,
+            TokenKind::MinusEq => TokenKind::MinusEq,
+            TokenKind::Dec => TokenKind::Dec,
+            TokenKind::Mult => TokenKind::Mult,
+            TokenKind::MultEq => TokenKind::MultEq,
+            TokenKind::Div => TokenKind::Div,
+            TokenKind::DivEq => TokenKind::DivEq,
+            TokenKind::Mod => TokenKind::Mod,
+            TokenKind::ModEq => TokenKind::ModEq,
+            TokenKind::BitAnd => TokenKind::BitAnd,
+            TokenKind::BitOr => TokenKind::BitOr,
+            TokenKind::BitXor => TokenKind::BitXor,
+            TokenKind::BitNot => TokenKind::BitNot,
+            TokenKind::And => TokenKind::And,
+            TokenKind::Or => TokenKind::Or,
+            TokenKind::Not => TokenKind::Not,
+            TokenKind::BitLeft => TokenKind::BitLeft,
+            TokenKind::BitRight => TokenKind::BitRight,
+            TokenKind::BitUnsRight => TokenKind::BitUnsRight,
+            TokenKind::Lt => TokenKind::Lt,
+            TokenKind::Le => TokenKind::Le,
+            TokenKind::Gt => TokenKind::Gt,
+            TokenKind::Ge => TokenKind::Ge,
+            TokenKind::EqEq => TokenKind::EqEq,
+            TokenKind::Neq => TokenKind::Neq,
+            TokenKind::Spaceship => TokenKind::Spaceship,
+            TokenKind::Eq => TokenKind::Eq,
+            TokenKind::Ins => TokenKind::Ins,
+            TokenKind::Comma => TokenKind::Comma,
+            TokenKind::Question => TokenKind::Question,
+            TokenKind::ParenOpen => TokenKind::ParenOpen,
+            TokenKind::ParenClose => TokenKind::ParenClose,
+            TokenKind::SquareOpen => TokenKind::SquareOpen,
+            TokenKind::SquareClose => TokenKind::SquareClose,
+            TokenKind::BraceOpen => TokenKind::BraceOpen,
+            TokenKind::BraceClose => TokenKind::BraceClose,
+            TokenKind::Dot => TokenKind::Dot,
+            TokenKind::Ellipsis => TokenKind::Ellipsis,
+            TokenKind::Colon => TokenKind::Colon,
+            TokenKind::Semicolon => TokenKind::Semicolon,
+            TokenKind::Scope => TokenKind::Scope,
+            TokenKind::At => TokenKind::At,
+            TokenKind::AttrOpen => TokenKind::AttrOpen,
+            TokenKind::AttrClose => TokenKind::AttrClose,
+        }
+    }
+
+    // Whether a keyword is one in a dialect, rather than an identifier. Other kinds are always
+    // allowed.
+    fn is_keyword_in(&self, dialect: Dialect) -> bool {
+        match self {
+            TokenKind::Delegate | TokenKind::Parent | TokenKind::Vargc | TokenKind::Vargv => {
+                dialect == Dialect::Squirrel2
+            }
+            TokenKind::Static | TokenKind::Rawcall | TokenKind::File | TokenKind::Line => {
+                dialect == Dialect::Squirrel3
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Writes the token exactly as it appears in the source.
+impl fmt::Display for TokenKind<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            TokenKind::Ident(value)
+            | TokenKind::Integer { text: value, .. }
+            | TokenKind::Float { text: value, .. }
+            | TokenKind::Char { text: value, .. }
+            | TokenKind::String { text: value, .. }
+            | TokenKind::VerbatimString { text: value, .. }
+            | TokenKind::Comment(value)
+            | TokenKind::MultiLineComment(value)
+            | TokenKind::DocComment(value)
+            | TokenKind::DocBlockComment(value)
+            | TokenKind::Whitespace(value)
+            | TokenKind::Error(value) => value,
+            TokenKind::Newline(ending) => ending.as_str(),
+            TokenKind::Eof => "",
+
+            TokenKind::Base => "base",
+            TokenKind::Break => "break",
+            TokenKind::Case => "case",
+            TokenKind::Catch => "catch",
+            TokenKind::Class => "class",
+            TokenKind::Clone => "clone",
+            TokenKind::Const => "const",
+            TokenKind::Constructor => "constructor",
+            TokenKind::Continue => "continue",
+            TokenKind::Default => "default",
+            TokenKind::Delegate => "delegate",
+            TokenKind::Delete => "delete",
+            TokenKind::Do => "do",
+            TokenKind::Else => "else",
+            TokenKind::Enum => "enum",
+            TokenKind::Extends => "extends",
+            TokenKind::False => "false",
+            TokenKind::File => "__FILE__",
+            TokenKind::For => "for",
+            TokenKind::Foreach => "foreach",
+            TokenKind::Function => "function",
+            TokenKind::If => "if",
+            TokenKind::In => "in",
+            TokenKind::Instanceof => "instanceof",
+            TokenKind::Line => "__LINE__",
+            TokenKind::Local => "local",
+            TokenKind::Null => "null",
+            TokenKind::Parent => "parent",
+            TokenKind::Rawcall => "rawcall",
+            TokenKind::Resume => "resume",
+            TokenKind::Return => "return",
+            TokenKind::Static => "static",
+            TokenKind::Switch => "switch",
+            TokenKind::This => "this",
+            TokenKind::Throw => "throw",
+            TokenKind::True => "true",
+            TokenKind::Try => "try",
+            TokenKind::Typeof => "typeof",
+            TokenKind::Vargc => "vargc",
+            TokenKind::Vargv => "vargv",
+            TokenKind::While => "while",
+            TokenKind::Yield => "yield",
+
+            TokenKind::Plus => "+",
+            TokenKind::PlusEq => "+=",
+            TokenKind::Inc => "++",
+            TokenKind::Minus => "-",
+            TokenKind::MinusEq => "-=",
+            TokenKind::Dec => "--",
+            TokenKind::Mult => "*",
+            TokenKind::MultEq => "*=",
+            TokenKind::Div => "/",
+            TokenKind::DivEq => "/=",
+            TokenKind::Mod => "%",
+            TokenKind::ModEq => "%=",
+
+            TokenKind::BitAnd => "&",
+            TokenKind::BitOr => "|",
+            TokenKind::BitXor => "^",
+            TokenKind::BitNot => "~",
+
+            TokenKind::And => "&&",
+            TokenKind::Or => "||",
+            TokenKind::Not => "!",
+
+            TokenKind::BitLeft => "<<",
+            TokenKind::BitRight => ">>",
+            TokenKind::BitUnsRight => ">>>",
+
+            TokenKind::Lt => "<",
+            TokenKind::Le => "<=",
+            TokenKind::Gt => ">",
+            TokenKind::Ge => ">=",
+            TokenKind::EqEq => "==",
+            TokenKind::Neq => "!=",
+            TokenKind::Spaceship => "<=>",
+
+            TokenKind::Eq => "=",
+            TokenKind::Ins => "<-",
+            TokenKind::Comma => ",",
+            TokenKind::Question => "?",
+
+            TokenKind::ParenOpen => "(",
+            TokenKind::ParenClose => ")",
+            TokenKind::SquareOpen => "[",
+            TokenKind::SquareClose => "]",
+            TokenKind::BraceOpen => "{",
+            TokenKind::BraceClose => "}",
+            TokenKind::Dot => ".",
+            TokenKind::Ellipsis => "...",
+            TokenKind::Colon => ":",
+            TokenKind::Semicolon => ";",
+            TokenKind::Scope => "::",
+            TokenKind::At => "@",
+            TokenKind::AttrOpen => "</",
+            TokenKind::AttrClose => "/>",
+        };
+        f.write_str(text)
+    }
+}
+
+/// The bytes ending a line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    /// `\n`, used on Unix.
+    Lf,
+    /// `\r\n`, used on Windows.
+    CrLf,
+    /// A lone `\r`, used on classic Mac OS.
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
 }
 
 #[derive(Constructor, Debug, PartialEq)]
-pub struct Token {
-    pub kind: TokenKind,
+pub struct Token<'src> {
+    pub kind: TokenKind<'src>,
     pub start_line: u32,
     pub start_column: u32,
     pub end_line: u32,
     pub end_column: u32,
+    /// The offset of the first byte of the token in the source.
+    pub start_byte: usize,
+    /// The offset of the byte after the token, so `start_byte..end_byte` is the token's text.
+    pub end_byte: usize,
 }
 
 /// An iterator which returns a stream of tokens from a source string.
 ///
+/// Tokens borrow their text from the source, so lexing allocates nothing per token. The only
+/// exceptions are the decoded values of strings with escape sequences and of verbatim strings with
+/// `""` in them. [`TokenKind::into_owned`] copies a token out of the source when it has to outlive it.
+///
 /// Normally, an iterator should only return `None` when the iteration has finished. This is not the
 /// case with the token stream, as after a `LexerError` pops up, the iteration terminates. There are
 /// two reasons for that:
@@ -122,29 +481,65 @@ pub struct Token {
 ///    parse. Thus, there is little point in returning any more tokens once an error has appeared.
 /// 2. If we choose to terminate early, we don't have to account for info recovery after an error,
 ///    e.g. column counts.
-pub struct Lexer {
-    source: Vec<u8>,
+///
+/// Editors and error reports want to see every error at once though, so a lexer created with
+/// [`Lexer::recovering`] keeps going instead. Every `LexerError` is followed by a
+/// [`TokenKind::Error`] covering the bytes which couldn't be lexed, after which lexing continues:
+///
+/// - An unclosed string or character literal is skipped up to the end of its line.
+/// - A string or character literal containing an error is skipped up to its closing quote.
+/// - A malformed number is skipped up to the next character which can't be part of a number.
+/// - An unexpected symbol is skipped on its own.
+/// - An unclosed multi-line comment or verbatim string takes up the rest of the source.
+pub struct Lexer<'src> {
+    source: &'src str,
     index: usize,
     line: u32,
     column: u32,
+    // The index, line and column the token being lexed started at
+    token_start: usize,
+    token_position: (u32, u32),
+    recovering: bool,
+    dialect: Dialect,
+    // The error token to return after a `LexerError` when recovering
+    error_token: Option<Token<'src>>,
 }
 
-impl Lexer {
-    pub fn new(source: &str) -> Self {
+impl<'src> Lexer<'src> {
+    pub fn new(source: &'src str) -> Self {
         Self {
-            source: source.as_bytes().to_owned(),
+            source,
             index: 0,
             line: 1,
             column: 1,
+            token_start: 0,
+            token_position: (1, 1),
+            recovering: false,
+            dialect: Dialect::Squirrel3,
+            error_token: None,
         }
     }
 
+    /// Creates a lexer which continues after errors, see [`Lexer`].
+    pub fn recovering(source: &'src str) -> Self {
+        Self {
+            recovering: true,
+            ..Self::new(source)
+        }
+    }
+
+    /// Lexes the keywords of a dialect, Squirrel 3 being the default. Keywords of other dialects
+    /// are identifiers.
+    pub fn with_dialect(self, dialect: Dialect) -> Self {
+        Self { dialect, ..self }
+    }
+
     fn current_byte(&self) -> Option<u8> {
-        self.source.get(self.index).copied()
+        self.source.as_bytes().get(self.index).copied()
     }
 
     fn peek_byte(&self) -> Option<u8> {
-        self.source.get(self.index + 1).copied()
+        self.source.as_bytes().get(self.index + 1).copied()
     }
 
     fn next_byte(&mut self, columns: bool) -> Option<u8> {
@@ -161,14 +556,10 @@ impl Lexer {
         self.column = 1;
     }
 
-    fn string_from(&self, start_index: usize) -> String {
-        // You should be banned from scripting if you somehow wrote bogus bytes
-        String::from_utf8_lossy(
-            self.source
-                .get(start_index..self.index)
-                .expect("range should be in bounds of source vector"),
-        )
-        .into_owned()
+    fn string_from(&self, start_index: usize) -> &'src str {
+        self.source
+            .get(start_index..self.index)
+            .expect("range should be in bounds of source and at character boundaries")
     }
 
     // Here, `char` signals that the callee is lexing a character code literal, which additional
@@ -221,6 +612,93 @@ impl Lexer {
         }
     }
 
+    // Lexes a number. A malformed one is an error rather than several tokens, e.g. `1.2.3` isn't
+    // `1.2` followed by `.3`. On errors, the column is left at the character to point at.
+    fn number(
+        &mut self,
+        start_index: usize,
+        start_column: u32,
+    ) -> Result<TokenKind<'src>, LexerErrorKind> {
+        let first = self.current_byte();
+        // Where the digits of an integer start and their radix, or `None` for a float
+        let digits = match self.next_byte(true) {
+            // A number starting with `0` is octal, unless its second digit is 8 or 9
+            Some(b'0'..=b'7') if first == Some(b'0') => {
+                while let Some(b'0'..=b'7') = self.next_byte(true) {}
+                if let Some(b'8' | b'9') = self.current_byte() {
+                    return Err(LexerErrorKind::InvalidOctal);
+                }
+                Some((1, 8))
+            }
+
+            Some(b'x' | b'X') if first == Some(b'0') => {
+                match self.next_byte(true) {
+                    Some(byte) if byte.is_ascii_hexdigit() => {}
+                    Some(_) => return Err(LexerErrorKind::EmptyHex),
+                    None => {
+                        self.column -= 1;
+                        return Err(LexerErrorKind::EmptyHex);
+                    }
+                }
+                while self
+                    .next_byte(true)
+                    .is_some_and(|byte| byte.is_ascii_hexdigit())
+                {}
+                Some((2, 16))
+            }
+
+            // The messiness of these matches lies in the different column handling depending on
+            // if `Some(_)` (e.g. 9.5eg, error should point at the "g") or
+            // `None` (e.g. 3.1e<stop>, error should point at the "e") is matched.
+            _ => {
+                let (mut fraction, mut exponent) = (false, false);
+                loop {
+                    match self.current_byte() {
+                        Some(b'.' | b'e' | b'E') if exponent => {
+                            return Err(LexerErrorKind::MalformedFloat);
+                        }
+                        Some(b'.') if fraction => return Err(LexerErrorKind::MalformedFloat),
+                        Some(b'.') => fraction = true,
+
+                        Some(b'e' | b'E') => {
+                            exponent = true;
+                            if let Some(b'+' | b'-') = self.next_byte(true) {
+                                self.next_byte(true);
+                            }
+                            match self.current_byte() {
+                                Some(b'0'..=b'9') => {}
+                                Some(_) => return Err(LexerErrorKind::MissingFloatExponent),
+                                None => {
+                                    self.column -= 1;
+                                    return Err(LexerErrorKind::MissingFloatExponent);
+                                }
+                            }
+                        }
+
+                        Some(b'0'..=b'9') => {}
+                        _ => break,
+                    }
+
+                    self.next_byte(true);
+                }
+                (!fraction && !exponent).then_some((0, 10))
+            }
+        };
+
+        if let Some(b'a'..=b'z' | b'A'..=b'Z' | b'_') = self.current_byte() {
+            return Err(LexerErrorKind::IdentAfterNumber);
+        }
+
+        let value = self.string_from(start_index);
+        let Some((prefix, radix)) = digits else {
+            return Ok(float(value));
+        };
+        integer(value, prefix, radix).ok_or_else(|| {
+            self.column = start_column;
+            LexerErrorKind::IntegerOverflow
+        })
+    }
+
     fn advance_hex_bytes(&mut self, columns: bool, max: u32) {
         for _ in 0..=max {
             match self.next_byte(columns) {
@@ -231,12 +709,10 @@ impl Lexer {
     }
 
     fn value_from_hex(&self, start_index: usize) -> Result<u32, LexerErrorKind> {
-        let src = str::from_utf8(
-            self.source
-                .get(start_index..self.index)
-                .expect("range should be in bounds of source vector"),
-        )
-        .expect("range should only contain hex digit bytes");
+        let src = self
+            .source
+            .get(start_index..self.index)
+            .expect("range should only contain hex digit bytes");
 
         match u32::from_str_radix(src, 16) {
             Ok(value) => Ok(value),
@@ -248,7 +724,7 @@ impl Lexer {
 
     fn advance_bytes_until_newline_or_eof(&mut self) {
         while let Some(byte) = self.next_byte(false) {
-            if byte == b'\n' {
+            if byte == b'\n' || byte == b'\r' {
                 break;
             }
         }
@@ -256,31 +732,133 @@ impl Lexer {
 
     fn create_on_line(
         &self,
-        kind: TokenKind,
+        kind: TokenKind<'src>,
         start_column: u32,
-    ) -> Option<Result<Token, LexerError>> {
+    ) -> Option<Result<Token<'src>, LexerError>> {
         Some(Ok(Token::new(
             kind,
             self.line,
             start_column,
             self.line,
             self.column - 1,
+            self.token_start,
+            self.index,
         )))
     }
 
-    fn stop_and_error(&mut self, kind: LexerErrorKind) -> Option<Result<Token, LexerError>> {
-        self.index = self.source.len();
-        Some(Err(LexerError::new(kind, self.line, self.column)))
+    fn stop_and_error(&mut self, kind: LexerErrorKind) -> Option<Result<Token<'src>, LexerError>> {
+        let end_byte = self.index.min(self.source.len());
+        let error = LexerError::new(kind, self.line, self.column, self.token_start, end_byte);
+        if self.recovering {
+            self.skip_error(&error.kind, end_byte);
+        } else {
+            self.index = self.source.len();
+        }
+        Some(Err(error))
+    }
+
+    // Skips past the bytes an error occurred in and stores the error token covering them
+    fn skip_error(&mut self, kind: &LexerErrorKind, index: usize) {
+        let source = self.source.as_bytes();
+        let skip_while = |mut index: usize, skip: &dyn Fn(u8) -> bool| {
+            while source.get(index).is_some_and(|&byte| skip(byte)) {
+                index += 1;
+            }
+            index
+        };
+
+        let end = match kind {
+            LexerErrorKind::UnclosedMultiLineComment
+            | LexerErrorKind::UnclosedVerbatimString
+            | LexerErrorKind::UnexpectedEof => source.len(),
+            LexerErrorKind::UnclosedString | LexerErrorKind::UnclosedChar => {
+                skip_while(index, &|byte| byte != b'\n' && byte != b'\r')
+            }
+            LexerErrorKind::CharOob
+            | LexerErrorKind::CharTooLong
+            | LexerErrorKind::EmptyChar
+            | LexerErrorKind::InvalidEscape
+            | LexerErrorKind::InvalidHexEscape => {
+                let quote = source[self.token_start];
+                let mut index = index;
+                loop {
+                    match source.get(index) {
+                        None | Some(b'\n' | b'\r') => break index,
+                        Some(&byte) if byte == quote => break index + 1,
+                        Some(b'\\') if index + 1 < source.len() => index += 2,
+                        Some(_) => index += 1,
+                    }
+                }
+            }
+            LexerErrorKind::InvalidOctal
+            | LexerErrorKind::MissingFloatExponent
+            | LexerErrorKind::EmptyHex
+            | LexerErrorKind::IntegerOverflow
+            | LexerErrorKind::IdentAfterNumber => {
+                skip_while(index, &|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+            }
+            LexerErrorKind::MalformedFloat => skip_while(index, &|byte| {
+                byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'.'
+            }),
+            LexerErrorKind::DoubleDot => index,
+            LexerErrorKind::UnexpectedSymbol => index + self.grapheme_len(index),
+        };
+        // Always make progress, and never stop within a character
+        let mut end = end.max(self.token_start + self.grapheme_len(self.token_start));
+        while end < source.len() && !(source[end].is_ascii() || source[end] >= 0xC0) {
+            end += 1;
+        }
+
+        let text = &self.source[self.token_start..end];
+        let (start_line, start_column) = self.token_position;
+        (self.line, self.column) = (start_line, start_column);
+        for grapheme in text.graphemes(true) {
+            if matches!(grapheme, "\n" | "\r\n" | "\r") {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+
+        self.error_token = Some(Token::new(
+            TokenKind::Error(text.into()),
+            start_line,
+            start_column,
+            self.line,
+            self.column.saturating_sub(1),
+            self.token_start,
+            end,
+        ));
+        self.index = end;
+    }
+
+    // The length of the grapheme starting at an index, or 0 at the end of the source
+    fn grapheme_len(&self, index: usize) -> usize {
+        let rest = self.source.get(index..).unwrap_or_default();
+        // Graphemes never span lines, except for "\r\n"
+        let line_end = rest
+            .bytes()
+            .skip(2)
+            .position(|byte| byte == b'\n' || byte == b'\r');
+        let rest = &rest[..line_end.map_or(rest.len(), |end| end + 2)];
+        rest.graphemes(true).next().map_or(0, str::len)
     }
 }
 
-impl Iterator for Lexer {
-    type Item = Result<Token, LexerError>;
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Result<Token<'src>, LexerError>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.error_token.take() {
+            return Some(Ok(token));
+        }
+
         let start_line = self.line;
         let start_column = self.column;
         let start_index = self.index;
+        self.token_start = start_index;
+        self.token_position = (start_line, start_column);
         match self.current_byte()? {
             // idents and keywords
             b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
@@ -293,17 +871,18 @@ impl Iterator for Lexer {
                 // This keyword lookup is from Inko, and it is likely as efficient as it gets
                 // without being too complex.
                 let kind = match value.len() {
-                    2 => match value.as_str() {
+                    2 => match value {
+                        "do" => TokenKind::Do,
                         "if" => TokenKind::If,
                         "in" => TokenKind::In,
-                        _ => TokenKind::Ident(value),
+                        _ => TokenKind::Ident(value.into()),
                     },
-                    3 => match value.as_str() {
+                    3 => match value {
                         "for" => TokenKind::For,
                         "try" => TokenKind::Try,
-                        _ => TokenKind::Ident(value),
+                        _ => TokenKind::Ident(value.into()),
                     },
-                    4 => match value.as_str() {
+                    4 => match value {
                         "base" => TokenKind::Base,
                         "case" => TokenKind::Case,
                         "else" => TokenKind::Else,
@@ -311,9 +890,9 @@ impl Iterator for Lexer {
                         "null" => TokenKind::Null,
                         "this" => TokenKind::This,
                         "true" => TokenKind::True,
-                        _ => TokenKind::Ident(value),
+                        _ => TokenKind::Ident(value.into()),
                     },
-                    5 => match value.as_str() {
+                    5 => match value {
                         "break" => TokenKind::Break,
                         "catch" => TokenKind::Catch,
                         "class" => TokenKind::Class,
@@ -322,39 +901,47 @@ impl Iterator for Lexer {
                         "false" => TokenKind::False,
                         "local" => TokenKind::Local,
                         "throw" => TokenKind::Throw,
+                        "vargc" => TokenKind::Vargc,
+                        "vargv" => TokenKind::Vargv,
                         "while" => TokenKind::While,
                         "yield" => TokenKind::Yield,
-                        _ => TokenKind::Ident(value),
+                        _ => TokenKind::Ident(value.into()),
                     },
-                    6 => match value.as_str() {
+                    6 => match value {
                         "delete" => TokenKind::Delete,
+                        "parent" => TokenKind::Parent,
                         "resume" => TokenKind::Resume,
                         "return" => TokenKind::Return,
                         "static" => TokenKind::Static,
                         "switch" => TokenKind::Switch,
                         "typeof" => TokenKind::Typeof,
-                        _ => TokenKind::Ident(value),
+                        _ => TokenKind::Ident(value.into()),
                     },
-                    7 => match value.as_str() {
+                    7 => match value {
                         "default" => TokenKind::Default,
                         "extends" => TokenKind::Extends,
                         "foreach" => TokenKind::Foreach,
                         "rawcall" => TokenKind::Rawcall,
-                        _ => TokenKind::Ident(value),
+                        _ => TokenKind::Ident(value.into()),
                     },
-                    8 => match value.as_str() {
+                    8 => match value {
                         "__FILE__" => TokenKind::File,
                         "__LINE__" => TokenKind::Line,
                         "continue" => TokenKind::Continue,
+                        "delegate" => TokenKind::Delegate,
                         "function" => TokenKind::Function,
-                        _ => TokenKind::Ident(value),
+                        _ => TokenKind::Ident(value.into()),
                     },
-                    10 | 11 => match value.as_str() {
+                    10 | 11 => match value {
                         "instanceof" => TokenKind::Instanceof,
                         "constructor" => TokenKind::Constructor,
-                        _ => TokenKind::Ident(value),
+                        _ => TokenKind::Ident(value.into()),
                     },
-                    _ => TokenKind::Ident(value),
+                    _ => TokenKind::Ident(value.into()),
+                };
+                let kind = match kind.is_keyword_in(self.dialect) {
+                    true => kind,
+                    false => TokenKind::Ident(self.string_from(start_index).into()),
                 };
 
                 self.create_on_line(kind, start_column)
@@ -378,7 +965,10 @@ impl Iterator for Lexer {
                                 }
                             }
 
-                            Some(b'\n') => {
+                            // The "\r" of a "\r\n" is skipped, the line ends at the "\n"
+                            Some(b'\r') if self.peek_byte() == Some(b'\n') => {}
+
+                            Some(b'\n' | b'\r') => {
                                 self.line += 1;
                                 last_line_start_index = self.index + 1;
                             }
@@ -420,12 +1010,19 @@ impl Iterator for Lexer {
                         return self.stop_and_error(LexerErrorKind::UnclosedMultiLineComment);
                     }
 
+                    let is_doc = value.starts_with("/**") && !value.starts_with("/***");
+                    let kind = match is_doc && value != "/**/" {
+                        true => TokenKind::DocBlockComment(value.into()),
+                        false => TokenKind::MultiLineComment(value.into()),
+                    };
                     Some(Ok(Token::new(
-                        TokenKind::MultiLineComment(value),
+                        kind,
                         start_line,
                         start_column,
                         self.line,
                         self.column - 1,
+                        start_index,
+                        self.index,
                     )))
                 }
 
@@ -434,7 +1031,11 @@ impl Iterator for Lexer {
                     self.advance_bytes_until_newline_or_eof();
                     let value = self.string_from(start_index);
                     self.column += value.graphemes(true).count() as u32;
-                    self.create_on_line(TokenKind::Comment(value), start_column)
+                    let kind = match value.starts_with("///") && !value.starts_with("////") {
+                        true => TokenKind::DocComment(value.into()),
+                        false => TokenKind::Comment(value.into()),
+                    };
+                    self.create_on_line(kind, start_column)
                 }
 
                 // "/="
@@ -444,6 +1045,13 @@ impl Iterator for Lexer {
                     self.create_on_line(TokenKind::DivEq, start_column)
                 }
 
+                // "/>", which closes class attributes
+                Some(b'>') => {
+                    self.next_byte(false);
+                    self.column += 2;
+                    self.create_on_line(TokenKind::AttrClose, start_column)
+                }
+
                 // "/"
                 _ => {
                     self.column += 1;
@@ -456,7 +1064,7 @@ impl Iterator for Lexer {
                 self.advance_bytes_until_newline_or_eof();
                 let value = self.string_from(start_index);
                 self.column += value.graphemes(true).count() as u32;
-                self.create_on_line(TokenKind::Comment(value), start_column)
+                self.create_on_line(TokenKind::Comment(value.into()), start_column)
             }
 
             b'@' => match self.next_byte(false) {
@@ -482,7 +1090,10 @@ impl Iterator for Lexer {
                                 }
                             }
 
-                            Some(b'\n') => {
+                            // The "\r" of a "\r\n" is skipped, the line ends at the "\n"
+                            Some(b'\r') if self.peek_byte() == Some(b'\n') => {}
+
+                            Some(b'\n' | b'\r') => {
                                 self.line += 1;
                                 last_line_start_index = self.index + 1;
                             }
@@ -520,11 +1131,13 @@ impl Iterator for Lexer {
                     }
 
                     Some(Ok(Token::new(
-                        TokenKind::Lit(value),
+                        verbatim_string(value),
                         start_line,
                         start_column,
                         self.line,
                         self.column - 1,
+                        start_index,
+                        self.index,
                     )))
                 }
 
@@ -536,89 +1149,10 @@ impl Iterator for Lexer {
             },
 
             // numerical literal
-            b'0'..=b'9' => {
-                if self.current_byte()? == b'0' {
-                    match self.next_byte(true) {
-                        Some(b'0'..=b'7') => {
-                            loop {
-                                match self.next_byte(true) {
-                                    Some(b'0'..=b'7') => {}
-                                    Some(b'8' | b'9') => {
-                                        return self.stop_and_error(LexerErrorKind::InvalidOctal);
-                                    }
-                                    _ => break,
-                                }
-                            }
-
-                            let value = self.string_from(start_index);
-                            return self.create_on_line(TokenKind::Lit(value), start_column);
-                        }
-
-                        Some(b'x' | b'X') => {
-                            while let Some(b'A'..=b'F' | b'a'..=b'f' | b'0'..=b'9') =
-                                self.next_byte(true)
-                            {}
-
-                            let value = self.string_from(start_index);
-                            return self.create_on_line(TokenKind::Lit(value), start_column);
-                        }
-
-                        Some(b'8' | b'9') => {
-                            while let Some(b'0'..=b'9') = self.next_byte(true) {}
-
-                            let value = self.string_from(start_index);
-                            return self.create_on_line(TokenKind::Lit(value), start_column);
-                        }
-
-                        Some(b'.' | b'e' | b'E') => {}
-
-                        _ => return self.create_on_line(TokenKind::Lit("0".into()), start_column),
-                    }
-                }
-
-                // The messiness of these matches lies in the different column handling depending on
-                // if `Some(_)` (e.g. 9.5eg, error should point at the "g") or
-                // `None` (e.g. 3.1e<stop>, error should point at the "e") is matched.
-                loop {
-                    match self.current_byte() {
-                        Some(b'e' | b'E') => match self.next_byte(true) {
-                            Some(b'0'..=b'9') => {}
-
-                            Some(b'+' | b'-') => match self.next_byte(true) {
-                                Some(b'0'..=b'9') => {}
-
-                                Some(_) => {
-                                    return self
-                                        .stop_and_error(LexerErrorKind::MissingFloatExponent);
-                                }
-
-                                None => {
-                                    self.column -= 1;
-                                    return self
-                                        .stop_and_error(LexerErrorKind::MissingFloatExponent);
-                                }
-                            },
-
-                            Some(_) => {
-                                return self.stop_and_error(LexerErrorKind::MissingFloatExponent);
-                            }
-
-                            None => {
-                                self.column -= 1;
-                                return self.stop_and_error(LexerErrorKind::MissingFloatExponent);
-                            }
-                        },
-
-                        Some(b'.' | b'0'..=b'9') => {}
-                        _ => break,
-                    }
-
-                    self.next_byte(true);
-                }
-
-                let value = self.string_from(start_index);
-                self.create_on_line(TokenKind::Lit(value), start_column)
-            }
+            b'0'..=b'9' => match self.number(start_index, start_column) {
+                Ok(kind) => self.create_on_line(kind, start_column),
+                Err(kind) => self.stop_and_error(kind),
+            },
 
             // "'...'" character code literal
             b'\'' => {
@@ -633,7 +1167,7 @@ impl Iterator for Lexer {
                         }
                     },
 
-                    Some(b'\n') | None => {
+                    Some(b'\n' | b'\r') | None => {
                         self.column -= 1;
                         return self.stop_and_error(LexerErrorKind::UnclosedChar);
                     }
@@ -647,10 +1181,10 @@ impl Iterator for Lexer {
                     Some(b'\'') => {
                         self.next_byte(true);
                         let value = self.string_from(start_index);
-                        self.create_on_line(TokenKind::Lit(value), start_column)
+                        self.create_on_line(char_code(value), start_column)
                     }
 
-                    Some(b'\n') | None => {
+                    Some(b'\n' | b'\r') | None => {
                         self.column -= 1;
                         self.stop_and_error(LexerErrorKind::UnclosedChar)
                     }
@@ -677,7 +1211,7 @@ impl Iterator for Lexer {
                             }
                         },
 
-                        Some(b'\n') | None => {
+                        Some(b'\n' | b'\r') | None => {
                             self.column +=
                                 self.string_from(start_index).graphemes(true).count() as u32 - 1;
                             return self.stop_and_error(LexerErrorKind::UnclosedString);
@@ -691,7 +1225,7 @@ impl Iterator for Lexer {
                 self.next_byte(false);
                 let value = self.string_from(start_index);
                 self.column += value.graphemes(true).count() as u32;
-                self.create_on_line(TokenKind::Lit(value), start_column)
+                self.create_on_line(string(value), start_column)
             }
 
             // "+", "+=" or "++"
@@ -822,11 +1356,13 @@ impl Iterator for Lexer {
                 self.create_on_line(kind, start_column)
             }
 
-            // "<", "<<", "<-", "<=", or "<=>"
+            // "<", "<<", "<-", "<=", "<=>" or "</", which opens class attributes. Like in the
+            // reference compiler, "</" takes precedence over a comment starting after the "<".
             b'<' => {
                 let kind = match self.next_byte(true) {
                     Some(b'<') => TokenKind::BitLeft,
                     Some(b'-') => TokenKind::Ins,
+                    Some(b'/') => TokenKind::AttrOpen,
                     Some(b'=') => match self.next_byte(true) {
                         Some(b'>') => TokenKind::Spaceship,
                         _ => TokenKind::Le,
@@ -940,14 +1476,30 @@ impl Iterator for Lexer {
             b' ' | b'\t' => {
                 while let Some(b' ' | b'\t') = self.next_byte(true) {}
                 let value = self.string_from(start_index);
-                self.create_on_line(TokenKind::Whitespace(value), start_column)
+                self.create_on_line(TokenKind::Whitespace(value.into()), start_column)
             }
 
-            b'\n' => {
-                self.column += 1;
-                let token = self.create_on_line(TokenKind::Newline, start_column);
+            b'\n' | b'\r' => {
+                let ending = match self.current_byte()? {
+                    b'\r' if self.peek_byte() == Some(b'\n') => {
+                        self.index += 1;
+                        LineEnding::CrLf
+                    }
+                    b'\r' => LineEnding::Cr,
+                    _ => LineEnding::Lf,
+                };
                 self.advance_line();
-                token
+
+                // "\r\n" is a single grapheme, so it only takes up one column
+                Some(Ok(Token::new(
+                    TokenKind::Newline(ending),
+                    start_line,
+                    start_column,
+                    start_line,
+                    start_column,
+                    start_index,
+                    self.index,
+                )))
             }
 
             _ => self.stop_and_error(LexerErrorKind::UnexpectedSymbol),
@@ -955,6 +1507,111 @@ impl Iterator for Lexer {
     }
 }
 
+// An integer literal, whose digits start after a prefix, or `None` if it doesn't fit in 64 bits.
+// Octal and hexadecimal integers may use the sign bit, e.g. `0xFFFFFFFFFFFFFFFF` is -1.
+fn integer(text: &str, prefix: usize, radix: u32) -> Option<TokenKind<'_>> {
+    let value = text[prefix..].chars().try_fold(0u64, |value, digit| {
+        let digit = digit
+            .to_digit(radix)
+            .expect("lexer should only accept digits of the radix");
+        value.checked_mul(radix.into())?.checked_add(digit.into())
+    })?;
+    let value = match radix {
+        10 => i64::try_from(value).ok()?,
+        _ => value as i64,
+    };
+    Some(TokenKind::Integer {
+        text: text.into(),
+        value,
+    })
+}
+
+fn float(text: &str) -> TokenKind<'_> {
+    let value = text.parse().expect("lexer should only accept valid floats");
+    TokenKind::Float {
+        text: text.into(),
+        value,
+    }
+}
+
+fn char_code(text: &str) -> TokenKind<'_> {
+    let character = unescape(&text[1..text.len() - 1]).chars().next();
+    let value = character.map_or(0, |character| u32::from(character).into());
+    TokenKind::Char {
+        text: text.into(),
+        value,
+    }
+}
+
+fn string(text: &str) -> TokenKind<'_> {
+    let value = unescape(&text[1..text.len() - 1]);
+    TokenKind::String {
+        text: text.into(),
+        value,
+    }
+}
+
+fn verbatim_string(text: &str) -> TokenKind<'_> {
+    let value = &text[2..text.len() - 1];
+    let value = match value.contains("\"\"") {
+        true => value.replace("\"\"", "\"").into(),
+        false => value.into(),
+    };
+    TokenKind::VerbatimString {
+        text: text.into(),
+        value,
+    }
+}
+
+// Decodes the escape sequences of a string or character code literal, which the lexer has checked
+// already. `\x`, `\u` and `\U` take up to 2, 4 and 8 hex digits, and give U+FFFD for codes which
+// aren't characters. `\x` gives the character of its code like the others, not a raw byte, which a
+// `str` can't hold. Text without escape sequences is borrowed as it is.
+fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return text.into();
+    }
+
+    let mut value = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find('\\') {
+        value.push_str(&rest[..index]);
+        let escape = &rest[index + 1..];
+        let (character, len) = match escape.as_bytes()[0] {
+            b't' => ('\t', 1),
+            b'a' => ('\x07', 1),
+            b'b' => ('\x08', 1),
+            b'n' => ('\n', 1),
+            b'r' => ('\r', 1),
+            b'v' => ('\x0B', 1),
+            b'f' => ('\x0C', 1),
+            b'0' => ('\0', 1),
+            kind @ (b'x' | b'u' | b'U') => {
+                let max = match kind {
+                    b'x' => 2,
+                    b'u' => 4,
+                    _ => 8,
+                };
+                let digits = escape.as_bytes()[1..]
+                    .iter()
+                    .take(max)
+                    .take_while(|byte| byte.is_ascii_hexdigit())
+                    .count();
+                let code = u32::from_str_radix(&escape[1..=digits], 16)
+                    .expect("lexer should only accept hex digits");
+                let character = char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER);
+                (character, 1 + digits)
+            }
+            // "\\", "\"" and "\'"
+            byte => (byte.into(), 1),
+        };
+        value.push(character);
+        rest = &escape[len..];
+    }
+    value.push_str(rest);
+    value.into()
+}
+
 #[derive(Debug, PartialEq)]
 pub enum LexerErrorKind {
     /// A symbol outside of the ASCII range (0 to 127 inclusive) was encountered in a character code
@@ -986,6 +1643,16 @@ pub enum LexerErrorKind {
     InvalidOctal,
     /// An exponent for floating point numbers written in scientific notation was missing.
     MissingFloatExponent,
+    /// A float literal had a second `.` or exponent, or a `.` after its exponent, e.g. `1.2.3` or
+    /// `1e5.2`.
+    MalformedFloat,
+    /// A hexadecimal number had no digits after its `0x`.
+    EmptyHex,
+    /// An integer literal didn't fit in 64 bits. Decimal integers can be at most `i64::MAX`, while
+    /// octal and hexadecimal ones may use the sign bit, e.g. `0xFFFFFFFFFFFFFFFF` is -1.
+    IntegerOverflow,
+    /// A letter or `_` directly followed a number, e.g. `3abc`.
+    IdentAfterNumber,
     /// A multi-line comment was unclosed.
     UnclosedMultiLineComment,
     /// A verbatim string was unclosed.
@@ -1002,25 +1669,109 @@ pub enum LexerErrorKind {
     UnexpectedEof,
 }
 
+impl fmt::Display for LexerErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            LexerErrorKind::CharOob => "character literal outside of the ASCII range",
+            LexerErrorKind::CharTooLong => "character literal with more than one character",
+            LexerErrorKind::EmptyChar => "empty character literal",
+            LexerErrorKind::UnclosedChar => "unclosed character literal",
+            LexerErrorKind::InvalidEscape => "invalid escape sequence",
+            LexerErrorKind::InvalidHexEscape => "invalid hexadecimal escape sequence",
+            LexerErrorKind::InvalidOctal => "invalid digit in octal number",
+            LexerErrorKind::MissingFloatExponent => "missing exponent in float literal",
+            LexerErrorKind::MalformedFloat => "malformed float literal",
+            LexerErrorKind::EmptyHex => "hexadecimal number without digits",
+            LexerErrorKind::IntegerOverflow => "integer literal too large",
+            LexerErrorKind::IdentAfterNumber => "identifier directly after a number",
+            LexerErrorKind::UnclosedMultiLineComment => "unclosed multi-line comment",
+            LexerErrorKind::UnclosedVerbatimString => "unclosed verbatim string",
+            LexerErrorKind::UnclosedString => "unclosed string",
+            LexerErrorKind::UnexpectedSymbol => "unexpected character",
+            LexerErrorKind::DoubleDot => "unexpected `..`",
+            LexerErrorKind::UnexpectedEof => "unexpected end of file",
+        };
+        f.write_str(message)
+    }
+}
+
 #[derive(Constructor, Debug, PartialEq)]
 pub struct LexerError {
     pub kind: LexerErrorKind,
     pub line: u32,
     pub column: u32,
+    /// The offset of the first byte of the token the error occurred in.
+    pub start_byte: usize,
+    /// The offset of the byte the error was detected at, so `start_byte..end_byte` is the part of
+    /// the token lexed before the error.
+    pub end_byte: usize,
 }
 
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}:{}", self.kind, self.line, self.column)
+    }
+}
+
+impl std::error::Error for LexerError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use LexerErrorKind::*;
+    use LineEnding::*;
     use TokenKind::*;
 
-    fn token(kind: TokenKind, start: (u32, u32), end: (u32, u32)) -> Token {
-        Token::new(kind, start.0, start.1, end.0, end.1)
+    // Byte spans are checked separately by `assert_spans!`, so they're left out here
+    fn token(kind: TokenKind<'_>, start: (u32, u32), end: (u32, u32)) -> Token<'_> {
+        Token::new(kind, start.0, start.1, end.0, end.1, 0, 0)
     }
 
     fn error(kind: LexerErrorKind, line: u32, column: u32) -> LexerError {
-        LexerError::new(kind, line, column)
+        LexerError::new(kind, line, column, 0, 0)
+    }
+
+    fn int_lit(text: &str, value: i64) -> TokenKind<'_> {
+        Integer {
+            text: text.into(),
+            value,
+        }
+    }
+
+    fn float_lit(text: &str, value: f64) -> TokenKind<'_> {
+        Float {
+            text: text.into(),
+            value,
+        }
+    }
+
+    fn char_lit(text: &str, value: i64) -> TokenKind<'_> {
+        Char {
+            text: text.into(),
+            value,
+        }
+    }
+
+    fn string_lit<'a>(text: &'a str, value: &'a str) -> TokenKind<'a> {
+        String {
+            text: text.into(),
+            value: value.into(),
+        }
+    }
+
+    fn verbatim_lit<'a>(text: &'a str, value: &'a str) -> TokenKind<'a> {
+        VerbatimString {
+            text: text.into(),
+            value: value.into(),
+        }
+    }
+
+    fn without_span(token: Token<'_>) -> Token<'_> {
+        Token {
+            start_byte: 0,
+            end_byte: 0,
+            ..token
+        }
     }
 
     macro_rules! assert_stream {
@@ -1030,11 +1781,29 @@ mod tests {
                 $token: expr
             ),+
         ) => {{
-            let vec_source = Lexer::new($source).collect::<Result<Vec<Token>, LexerError>>().unwrap();
+            let vec_source = Lexer::new($source)
+                .map(|token| token.map(without_span))
+                .collect::<Result<Vec<Token>, LexerError>>()
+                .unwrap();
             assert_eq!(vec_source, vec![$($token,)+]);
         }};
     }
 
+    macro_rules! assert_spans {
+        (
+            $source: expr,
+            $(
+                $span: expr
+            ),+
+        ) => {{
+            let spans = Lexer::new($source)
+                .map(|token| token.map(|token| token.start_byte..token.end_byte))
+                .collect::<Result<Vec<_>, LexerError>>()
+                .unwrap();
+            assert_eq!(spans, vec![$($span,)+]);
+        }};
+    }
+
     macro_rules! assert_error {
         (
             $source: expr,
@@ -1045,6 +1814,11 @@ mod tests {
             let source = Lexer::new($source)
                 .collect::<Result<Vec<Token>, LexerError>>()
                 .unwrap_err();
+            let source = LexerError {
+                start_byte: 0,
+                end_byte: 0,
+                ..source
+            };
             assert_eq!(source, error($kind, $line, $column));
         }};
     }
@@ -1099,6 +1873,7 @@ mod tests {
         assert_stream!("continue", token(Continue, (1, 1), (1, 8)));
         assert_stream!("default", token(Default, (1, 1), (1, 7)));
         assert_stream!("delete", token(Delete, (1, 1), (1, 6)));
+        assert_stream!("do", token(Do, (1, 1), (1, 2)));
         assert_stream!("else", token(Else, (1, 1), (1, 4)));
         assert_stream!("enum", token(Enum, (1, 1), (1, 4)));
         assert_stream!("extends", token(Extends, (1, 1), (1, 7)));
@@ -1127,6 +1902,46 @@ mod tests {
         assert_stream!("yield", token(Yield, (1, 1), (1, 5)));
     }
 
+    #[test]
+    fn dialects() {
+        let kinds = |source, dialect| {
+            Lexer::new(source)
+                .with_dialect(dialect)
+                .map(|token| token.unwrap().kind)
+                .filter(|kind| !kind.is_trivia())
+                .collect::<Vec<_>>()
+        };
+        let ident = |name: &'static str| TokenKind::Ident(name.into());
+
+        let source = "delegate parent vargc vargv static rawcall __FILE__ __LINE__";
+        assert_eq!(
+            kinds(source, Dialect::Squirrel2),
+            [
+                TokenKind::Delegate,
+                TokenKind::Parent,
+                TokenKind::Vargc,
+                TokenKind::Vargv,
+                ident("static"),
+                ident("rawcall"),
+                ident("__FILE__"),
+                ident("__LINE__"),
+            ]
+        );
+        assert_eq!(
+            kinds(source, Dialect::Squirrel3),
+            [
+                ident("delegate"),
+                ident("parent"),
+                ident("vargc"),
+                ident("vargv"),
+                TokenKind::Static,
+                TokenKind::Rawcall,
+                TokenKind::File,
+                TokenKind::Line,
+            ]
+        );
+    }
+
     #[test]
     fn symbols() {
         assert_stream!("+", token(Plus, (1, 1), (1, 1)));
@@ -1180,11 +1995,42 @@ mod tests {
         assert_stream!(";", token(Semicolon, (1, 1), (1, 1)));
         assert_stream!("::", token(Scope, (1, 1), (1, 2)));
         assert_stream!("@", token(At, (1, 1), (1, 1)));
+        assert_stream!("</", token(AttrOpen, (1, 1), (1, 2)));
+        assert_stream!("/>", token(AttrClose, (1, 1), (1, 2)));
+    }
+
+    #[test]
+    fn attributes() {
+        assert_stream!(
+            "</a=1/>",
+            token(AttrOpen, (1, 1), (1, 2)),
+            token(Ident("a".into()), (1, 3), (1, 3)),
+            token(Eq, (1, 4), (1, 4)),
+            token(int_lit("1", 1), (1, 5), (1, 5)),
+            token(AttrClose, (1, 6), (1, 7))
+        );
+        assert_stream!(
+            "< / />",
+            token(Lt, (1, 1), (1, 1)),
+            token(Whitespace(" ".into()), (1, 2), (1, 2)),
+            token(Div, (1, 3), (1, 3)),
+            token(Whitespace(" ".into()), (1, 4), (1, 4)),
+            token(AttrClose, (1, 5), (1, 6))
+        );
+        assert_stream!(
+            "a</*b*/",
+            token(Ident("a".into()), (1, 1), (1, 1)),
+            token(AttrOpen, (1, 2), (1, 3)),
+            token(Mult, (1, 4), (1, 4)),
+            token(Ident("b".into()), (1, 5), (1, 5)),
+            token(Mult, (1, 6), (1, 6)),
+            token(Div, (1, 7), (1, 7))
+        );
     }
 
     #[test]
     fn string_empty() {
-        assert_stream!("\"\"", token(Lit("\"\"".into()), (1, 1), (1, 2)));
+        assert_stream!("\"\"", token(string_lit("\"\"", ""), (1, 1), (1, 2)));
     }
 
     #[test]
@@ -1192,119 +2038,244 @@ mod tests {
         // general netprop
         assert_stream!(
             "\"m_iszMvMPopfileName\"",
-            token(Lit("\"m_iszMvMPopfileName\"".into()), (1, 1), (1, 21))
+            token(
+                string_lit("\"m_iszMvMPopfileName\"", "m_iszMvMPopfileName"),
+                (1, 1),
+                (1, 21)
+            )
         );
         // unicode characters
         assert_stream!(
             "\"viele Möglichkeiten\"",
-            token(Lit("\"viele Möglichkeiten\"".into()), (1, 1), (1, 21))
+            token(
+                string_lit("\"viele Möglichkeiten\"", "viele Möglichkeiten"),
+                (1, 1),
+                (1, 21)
+            )
         );
     }
 
     #[test]
     fn char_code() {
-        assert_stream!("'_'", token(Lit("'_'".into()), (1, 1), (1, 3)));
-        assert_stream!("'a'", token(Lit("'a'".into()), (1, 1), (1, 3)));
-        assert_stream!("'Z'", token(Lit("'Z'".into()), (1, 1), (1, 3)));
+        assert_stream!("'_'", token(char_lit("'_'", 95), (1, 1), (1, 3)));
+        assert_stream!("'a'", token(char_lit("'a'", 97), (1, 1), (1, 3)));
+        assert_stream!("'Z'", token(char_lit("'Z'", 90), (1, 1), (1, 3)));
     }
 
     #[test]
     fn escape_sequences() {
         // string literals
-        assert_stream!(r#""\t""#, token(Lit(r#""\t""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\a""#, token(Lit(r#""\a""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\b""#, token(Lit(r#""\b""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\n""#, token(Lit(r#""\n""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\r""#, token(Lit(r#""\r""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\v""#, token(Lit(r#""\v""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\f""#, token(Lit(r#""\f""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\\""#, token(Lit(r#""\\""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\"""#, token(Lit(r#""\"""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\'""#, token(Lit(r#""\'""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\0""#, token(Lit(r#""\0""#.into()), (1, 1), (1, 4)));
-        assert_stream!(r#""\xf""#, token(Lit(r#""\xf""#.into()), (1, 1), (1, 5)));
-        assert_stream!(r#""\xFF""#, token(Lit(r#""\xFF""#.into()), (1, 1), (1, 6)));
-        assert_stream!(r#""\uf""#, token(Lit(r#""\uf""#.into()), (1, 1), (1, 5)));
+        assert_stream!(
+            r#""\t""#,
+            token(string_lit(r#""\t""#, "\t"), (1, 1), (1, 4))
+        );
+        assert_stream!(
+            r#""\a""#,
+            token(string_lit(r#""\a""#, "\x07"), (1, 1), (1, 4))
+        );
+        assert_stream!(
+            r#""\b""#,
+            token(string_lit(r#""\b""#, "\x08"), (1, 1), (1, 4))
+        );
+        assert_stream!(
+            r#""\n""#,
+            token(string_lit(r#""\n""#, "\n"), (1, 1), (1, 4))
+        );
+        assert_stream!(
+            r#""\r""#,
+            token(string_lit(r#""\r""#, "\r"), (1, 1), (1, 4))
+        );
+        assert_stream!(
+            r#""\v""#,
+            token(string_lit(r#""\v""#, "\x0B"), (1, 1), (1, 4))
+        );
+        assert_stream!(
+            r#""\f""#,
+            token(string_lit(r#""\f""#, "\x0C"), (1, 1), (1, 4))
+        );
+        assert_stream!(
+            r#""\\""#,
+            token(string_lit(r#""\\""#, "\\"), (1, 1), (1, 4))
+        );
+        assert_stream!(
+            r#""\"""#,
+            token(string_lit(r#""\"""#, "\""), (1, 1), (1, 4))
+        );
+        assert_stream!(r#""\'""#, token(string_lit(r#""\'""#, "'"), (1, 1), (1, 4)));
+        assert_stream!(
+            r#""\0""#,
+            token(string_lit(r#""\0""#, "\0"), (1, 1), (1, 4))
+        );
+        assert_stream!(
+            r#""\xf""#,
+            token(string_lit(r#""\xf""#, "\x0F"), (1, 1), (1, 5))
+        );
+        assert_stream!(
+            r#""\xFF""#,
+            token(string_lit(r#""\xFF""#, "\u{FF}"), (1, 1), (1, 6))
+        );
+        assert_stream!(
+            r#""\uf""#,
+            token(string_lit(r#""\uf""#, "\x0F"), (1, 1), (1, 5))
+        );
         assert_stream!(
             r#""\uFFFF""#,
-            token(Lit(r#""\uFFFF""#.into()), (1, 1), (1, 8))
+            token(string_lit(r#""\uFFFF""#, "\u{FFFF}"), (1, 1), (1, 8))
+        );
+        assert_stream!(
+            r#""\Uf""#,
+            token(string_lit(r#""\Uf""#, "\x0F"), (1, 1), (1, 5))
         );
-        assert_stream!(r#""\Uf""#, token(Lit(r#""\Uf""#.into()), (1, 1), (1, 5)));
         assert_stream!(
             r#""\UFFFFFFFF""#,
-            token(Lit(r#""\UFFFFFFFF""#.into()), (1, 1), (1, 12))
+            token(string_lit(r#""\UFFFFFFFF""#, "\u{FFFD}"), (1, 1), (1, 12))
         );
 
         // character code literals
-        assert_stream!("'\\t'", token(Lit("'\\t'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\a'", token(Lit("'\\a'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\b'", token(Lit("'\\b'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\n'", token(Lit("'\\n'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\r'", token(Lit("'\\r'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\v'", token(Lit("'\\v'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\f'", token(Lit("'\\f'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\\\'", token(Lit("'\\\\'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\\"'", token(Lit("'\\\"'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\''", token(Lit("'\\''".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\0'", token(Lit("'\\0'".into()), (1, 1), (1, 4)));
-        assert_stream!("'\\xf'", token(Lit("'\\xf'".into()), (1, 1), (1, 5)));
-        assert_stream!("'\\xFF'", token(Lit("'\\xFF'".into()), (1, 1), (1, 6)));
-        assert_stream!("'\\uf'", token(Lit("'\\uf'".into()), (1, 1), (1, 5)));
-        assert_stream!("'\\u007F'", token(Lit("'\\u007F'".into()), (1, 1), (1, 8)));
-        assert_stream!("'\\Uf'", token(Lit("'\\Uf'".into()), (1, 1), (1, 5)));
+        assert_stream!("'\\t'", token(char_lit("'\\t'", 9), (1, 1), (1, 4)));
+        assert_stream!("'\\a'", token(char_lit("'\\a'", 7), (1, 1), (1, 4)));
+        assert_stream!("'\\b'", token(char_lit("'\\b'", 8), (1, 1), (1, 4)));
+        assert_stream!("'\\n'", token(char_lit("'\\n'", 10), (1, 1), (1, 4)));
+        assert_stream!("'\\r'", token(char_lit("'\\r'", 13), (1, 1), (1, 4)));
+        assert_stream!("'\\v'", token(char_lit("'\\v'", 11), (1, 1), (1, 4)));
+        assert_stream!("'\\f'", token(char_lit("'\\f'", 12), (1, 1), (1, 4)));
+        assert_stream!("'\\\\'", token(char_lit("'\\\\'", 92), (1, 1), (1, 4)));
+        assert_stream!("'\\\"'", token(char_lit("'\\\"'", 34), (1, 1), (1, 4)));
+        assert_stream!("'\\''", token(char_lit("'\\''", 39), (1, 1), (1, 4)));
+        assert_stream!("'\\0'", token(char_lit("'\\0'", 0), (1, 1), (1, 4)));
+        assert_stream!("'\\xf'", token(char_lit("'\\xf'", 15), (1, 1), (1, 5)));
+        assert_stream!("'\\xFF'", token(char_lit("'\\xFF'", 255), (1, 1), (1, 6)));
+        assert_stream!("'\\uf'", token(char_lit("'\\uf'", 15), (1, 1), (1, 5)));
+        assert_stream!(
+            "'\\u007F'",
+            token(char_lit("'\\u007F'", 127), (1, 1), (1, 8))
+        );
+        assert_stream!("'\\Uf'", token(char_lit("'\\Uf'", 15), (1, 1), (1, 5)));
         assert_stream!(
             "'\\U0000007F'",
-            token(Lit("'\\U0000007F'".into()), (1, 1), (1, 12))
+            token(char_lit("'\\U0000007F'", 127), (1, 1), (1, 12))
         );
     }
 
     #[test]
     fn numbers() {
         // octals
-        assert_stream!("0", token(Lit("0".into()), (1, 1), (1, 1)));
-        assert_stream!("000", token(Lit("000".into()), (1, 1), (1, 3)));
-        assert_stream!("07127", token(Lit("07127".into()), (1, 1), (1, 5)));
-        assert_stream!("003400005", token(Lit("003400005".into()), (1, 1), (1, 9)));
+        assert_stream!("0", token(int_lit("0", 0), (1, 1), (1, 1)));
+        assert_stream!("000", token(int_lit("000", 0), (1, 1), (1, 3)));
+        assert_stream!("07127", token(int_lit("07127", 3671), (1, 1), (1, 5)));
+        assert_stream!(
+            "003400005",
+            token(int_lit("003400005", 917509), (1, 1), (1, 9))
+        );
 
         // decimals
-        assert_stream!("2", token(Lit("2".into()), (1, 1), (1, 1)));
-        assert_stream!("420", token(Lit("420".into()), (1, 1), (1, 3)));
-        assert_stream!("1337", token(Lit("1337".into()), (1, 1), (1, 4)));
-        assert_stream!("56789", token(Lit("56789".into()), (1, 1), (1, 5)));
+        assert_stream!("2", token(int_lit("2", 2), (1, 1), (1, 1)));
+        assert_stream!("420", token(int_lit("420", 420), (1, 1), (1, 3)));
+        assert_stream!("1337", token(int_lit("1337", 1337), (1, 1), (1, 4)));
+        assert_stream!("56789", token(int_lit("56789", 56789), (1, 1), (1, 5)));
 
         // hexadecimals
-        assert_stream!("0x", token(Lit("0x".into()), (1, 1), (1, 2)));
-        assert_stream!("0X", token(Lit("0X".into()), (1, 1), (1, 2)));
-        assert_stream!("0x012aBc", token(Lit("0x012aBc".into()), (1, 1), (1, 8)));
-        assert_stream!("0X034CdE", token(Lit("0X034CdE".into()), (1, 1), (1, 8)));
-        assert_stream!("0x567AbCd", token(Lit("0x567AbCd".into()), (1, 1), (1, 9)));
-        assert_stream!("0X890cDeF", token(Lit("0X890cDeF".into()), (1, 1), (1, 9)));
+        assert_stream!(
+            "0x012aBc",
+            token(int_lit("0x012aBc", 76476), (1, 1), (1, 8))
+        );
+        assert_stream!(
+            "0X034CdE",
+            token(int_lit("0X034CdE", 216286), (1, 1), (1, 8))
+        );
+        assert_stream!(
+            "0x567AbCd",
+            token(int_lit("0x567AbCd", 90680269), (1, 1), (1, 9))
+        );
+        assert_stream!(
+            "0X890cDeF",
+            token(int_lit("0X890cDeF", 143707631), (1, 1), (1, 9))
+        );
 
         // floats
-        assert_stream!("0.", token(Lit("0.".into()), (1, 1), (1, 2)));
-        assert_stream!("0.0", token(Lit("0.0".into()), (1, 1), (1, 3)));
-        assert_stream!("0.015", token(Lit("0.015".into()), (1, 1), (1, 5)));
-        assert_stream!("2.71", token(Lit("2.71".into()), (1, 1), (1, 4)));
-        assert_stream!("3e8", token(Lit("3e8".into()), (1, 1), (1, 3)));
-        assert_stream!("6.02e+23", token(Lit("6.02e+23".into()), (1, 1), (1, 8)));
-        assert_stream!("1.6e-19", token(Lit("1.6e-19".into()), (1, 1), (1, 7)));
-        assert_stream!("44.1E3", token(Lit("44.1E3".into()), (1, 1), (1, 6)));
-        assert_stream!("192E+3", token(Lit("192E+3".into()), (1, 1), (1, 6)));
-        assert_stream!("1.38E-23", token(Lit("1.38E-23".into()), (1, 1), (1, 8)));
-
-        // insane floats that somehow lexes
-        assert_stream!("5.35....1", token(Lit("5.35....1".into()), (1, 1), (1, 9)));
-        assert_stream!("0...e2", token(Lit("0...e2".into()), (1, 1), (1, 6)));
+        assert_stream!("0.", token(float_lit("0.", 0.0), (1, 1), (1, 2)));
+        assert_stream!("0.0", token(float_lit("0.0", 0.0), (1, 1), (1, 3)));
+        assert_stream!("0.015", token(float_lit("0.015", 0.015), (1, 1), (1, 5)));
+        assert_stream!("2.71", token(float_lit("2.71", 2.71), (1, 1), (1, 4)));
+        assert_stream!("3e8", token(float_lit("3e8", 3e8), (1, 1), (1, 3)));
+        assert_stream!(
+            "6.02e+23",
+            token(float_lit("6.02e+23", 6.02e23), (1, 1), (1, 8))
+        );
+        assert_stream!(
+            "1.6e-19",
+            token(float_lit("1.6e-19", 1.6e-19), (1, 1), (1, 7))
+        );
+        assert_stream!("44.1E3", token(float_lit("44.1E3", 44.1e3), (1, 1), (1, 6)));
+        assert_stream!("192E+3", token(float_lit("192E+3", 192e3), (1, 1), (1, 6)));
         assert_stream!(
-            "1e5.125..e8...e-1..E+12.0e+10",
-            token(Lit("1e5.125..e8...e-1..E+12.0e+10".into()), (1, 1), (1, 29))
+            "1.38E-23",
+            token(float_lit("1.38E-23", 1.38e-23), (1, 1), (1, 8))
         );
+
+        assert_stream!("1.e5", token(float_lit("1.e5", 1e5), (1, 1), (1, 4)));
+        assert_stream!("08.5", token(float_lit("08.5", 8.5), (1, 1), (1, 4)));
         assert_stream!(
-            "4.5e+4a-2",
-            token(Lit("4.5e+4".into()), (1, 1), (1, 6)),
-            token(Ident("a".into()), (1, 7), (1, 7)),
-            token(Minus, (1, 8), (1, 8)),
-            token(Lit("2".into()), (1, 9), (1, 9))
+            "4.5e+4-2",
+            token(float_lit("4.5e+4", 4.5e4), (1, 1), (1, 6)),
+            token(Minus, (1, 7), (1, 7)),
+            token(int_lit("2", 2), (1, 8), (1, 8))
+        );
+    }
+
+    #[test]
+    fn borrowed() {
+        let kinds: Vec<_> = Lexer::new(r#"a "b" "c\n" @"d""" // e"#)
+            .map(|token| token.unwrap().kind)
+            .collect();
+        let borrowed = |text: &Cow<'_, str>| matches!(text, Cow::Borrowed(_));
+        assert!(kinds.iter().all(|kind| match kind {
+            Ident(text) | Whitespace(text) | Comment(text) => borrowed(text),
+            String { text, .. } | VerbatimString { text, .. } => borrowed(text),
+            _ => false,
+        }));
+        let values: Vec<_> = kinds
+            .iter()
+            .filter_map(|kind| match kind {
+                String { value, .. } | VerbatimString { value, .. } => Some(borrowed(value)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(values, [true, false, false]);
+    }
+
+    #[test]
+    fn values() {
+        assert_stream!("0x1F", token(int_lit("0x1F", 31), (1, 1), (1, 4)));
+        assert_stream!("072", token(int_lit("072", 58), (1, 1), (1, 3)));
+        assert_stream!("08", token(int_lit("08", 8), (1, 1), (1, 2)));
+        assert_stream!("3.1e5", token(float_lit("3.1e5", 3.1e5), (1, 1), (1, 5)));
+        assert_stream!(
+            "0xFFFFFFFFFFFFFFFF",
+            token(int_lit("0xFFFFFFFFFFFFFFFF", -1), (1, 1), (1, 18))
+        );
+
+        assert_stream!(r"'\x41'", token(char_lit(r"'\x41'", 65), (1, 1), (1, 6)));
+        assert_stream!(
+            r#""a\tb\x41cä\xFFF""#,
+            token(
+                string_lit(r#""a\tb\x41cä\xFFF""#, "a\tbAcä\u{FF}F"),
+                (1, 1),
+                (1, 17)
+            )
+        );
+        // The character U+00FF, not the byte 0xFF
+        assert_stream!(
+            r#""\xFF""#,
+            token(string_lit(r#""\xFF""#, "ÿ"), (1, 1), (1, 6))
+        );
+        assert_stream!(
+            r#""\uD800""#,
+            token(string_lit(r#""\uD800""#, "\u{FFFD}"), (1, 1), (1, 8))
+        );
+        assert_stream!(
+            r#"@"a ""b""""#,
+            token(verbatim_lit(r#"@"a ""b""""#, "a \"b\""), (1, 1), (1, 10))
         );
     }
 
@@ -1324,7 +2295,7 @@ mod tests {
         assert_stream!(
             "// viele Möglichkeiten\n",
             token(Comment("// viele Möglichkeiten".into()), (1, 1), (1, 22)),
-            token(Newline, (1, 23), (1, 23))
+            token(Newline(Lf), (1, 23), (1, 23))
         );
 
         assert_stream!(
@@ -1335,7 +2306,7 @@ mod tests {
         assert_stream!(
             "# viele Möglichkeiten\n",
             token(Comment("# viele Möglichkeiten".into()), (1, 1), (1, 21)),
-            token(Newline, (1, 22), (1, 22))
+            token(Newline(Lf), (1, 22), (1, 22))
         );
     }
 
@@ -1371,7 +2342,7 @@ mod tests {
                 *  viele
                 *  Möglichkeiten */"#,
             token(
-                MultiLineComment(
+                DocBlockComment(
             r#"/** ganz
                 *  viele
                 *  Möglichkeiten */"#.into()
@@ -1382,23 +2353,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn doc_comments() {
+        let kinds = |source| -> Vec<_> {
+            Lexer::new(source)
+                .map(|token| token.unwrap().kind)
+                .filter(|kind| !matches!(kind, Newline(_)))
+                .collect()
+        };
+        assert_eq!(
+            kinds("/// a\n//// b\n// c\n#/ d"),
+            [
+                DocComment("/// a".into()),
+                Comment("//// b".into()),
+                Comment("// c".into()),
+                Comment("#/ d".into()),
+            ]
+        );
+        assert_eq!(
+            kinds("/** a */\n/*** b */\n/**/\n/* c */\n/***/"),
+            [
+                DocBlockComment("/** a */".into()),
+                MultiLineComment("/*** b */".into()),
+                MultiLineComment("/**/".into()),
+                MultiLineComment("/* c */".into()),
+                MultiLineComment("/***/".into()),
+            ]
+        );
+        assert!(DocComment("///".into()).is_trivia());
+    }
+
     #[test]
     fn verbatim_string_empty() {
-        assert_stream!("@\"\"", token(Lit("@\"\"".into()), (1, 1), (1, 3)));
+        assert_stream!("@\"\"", token(verbatim_lit("@\"\"", ""), (1, 1), (1, 3)));
     }
 
     #[test]
     fn verbatim_string() {
         assert_stream!(
             "@\"viele Möglichkeiten\"",
-            token(Lit("@\"viele Möglichkeiten\"".into()), (1, 1), (1, 22))
+            token(
+                verbatim_lit("@\"viele Möglichkeiten\"", "viele Möglichkeiten"),
+                (1, 1),
+                (1, 22)
+            )
         );
         assert_stream!(
             r#"@"ganz
 viele
 Möglichkeiten""#,
             token(
-                Lit("@\"ganz\nviele\nMöglichkeiten\"".into()),
+                verbatim_lit(
+                    "@\"ganz\nviele\nMöglichkeiten\"",
+                    "ganz\nviele\nMöglichkeiten"
+                ),
                 (1, 1),
                 (3, 14)
             )
@@ -1406,14 +2414,21 @@ Möglichkeiten""#,
         assert_stream!(
             r#"@"no ""escapes"", \R\E\A\L\L\Y!""#,
             token(
-                Lit(r#"@"no ""escapes"", \R\E\A\L\L\Y!""#.into()),
+                verbatim_lit(
+                    r#"@"no ""escapes"", \R\E\A\L\L\Y!""#,
+                    "no \"escapes\", \\R\\E\\A\\L\\L\\Y!"
+                ),
                 (1, 1),
                 (1, 32)
             )
         );
         assert_stream!(
             r#"@"no ""escapes""""#,
-            token(Lit(r#"@"no ""escapes""""#.into()), (1, 1), (1, 17))
+            token(
+                verbatim_lit(r#"@"no ""escapes""""#, "no \"escapes\""),
+                (1, 1),
+                (1, 17)
+            )
         );
     }
 
@@ -1436,13 +2451,52 @@ Möglichkeiten""#,
 
     #[test]
     fn newline() {
-        assert_stream!("\n", token(Newline, (1, 1), (1, 1)));
+        assert_stream!("\n", token(Newline(Lf), (1, 1), (1, 1)));
         assert_stream!(
             "a\nbc\n",
             token(Ident("a".into()), (1, 1), (1, 1)),
-            token(Newline, (1, 2), (1, 2)),
+            token(Newline(Lf), (1, 2), (1, 2)),
             token(Ident("bc".into()), (2, 1), (2, 2)),
-            token(Newline, (2, 3), (2, 3))
+            token(Newline(Lf), (2, 3), (2, 3))
+        );
+    }
+
+    #[test]
+    fn newline_crlf_and_cr() {
+        assert_stream!("\r\n", token(Newline(CrLf), (1, 1), (1, 1)));
+        assert_stream!("\r", token(Newline(Cr), (1, 1), (1, 1)));
+        assert_stream!(
+            "a\r\nb\rc\n\r\n",
+            token(Ident("a".into()), (1, 1), (1, 1)),
+            token(Newline(CrLf), (1, 2), (1, 2)),
+            token(Ident("b".into()), (2, 1), (2, 1)),
+            token(Newline(Cr), (2, 2), (2, 2)),
+            token(Ident("c".into()), (3, 1), (3, 1)),
+            token(Newline(Lf), (3, 2), (3, 2)),
+            token(Newline(CrLf), (4, 1), (4, 1))
+        );
+    }
+
+    #[test]
+    fn crlf_in_comments_and_verbatim_strings() {
+        assert_stream!(
+            "// a\r\n",
+            token(Comment("// a".into()), (1, 1), (1, 4)),
+            token(Newline(CrLf), (1, 5), (1, 5))
+        );
+        assert_stream!(
+            "# a\rb",
+            token(Comment("# a".into()), (1, 1), (1, 3)),
+            token(Newline(Cr), (1, 4), (1, 4)),
+            token(Ident("b".into()), (2, 1), (2, 1))
+        );
+        assert_stream!(
+            "/* a\r\nb\rc */",
+            token(MultiLineComment("/* a\r\nb\rc */".into()), (1, 1), (3, 4))
+        );
+        assert_stream!(
+            "@\"a\r\nbc\"",
+            token(verbatim_lit("@\"a\r\nbc\"", "a\r\nbc"), (1, 1), (2, 3))
         );
     }
 
@@ -1463,6 +2517,8 @@ Möglichkeiten""#,
         assert_error!("\"hä?", UnclosedString, 1, 4);
         assert_error!("\"\n", UnclosedString, 1, 1);
         assert_error!("\"hä?\n", UnclosedString, 1, 4);
+        assert_error!("\"hä?\r\n", UnclosedString, 1, 4);
+        assert_error!("\"hä?\r", UnclosedString, 1, 4);
     }
 
     #[test]
@@ -1493,6 +2549,7 @@ Möglichkeiten""#,
         assert_error!("'\n", UnclosedChar, 1, 1);
         assert_error!("'a", UnclosedChar, 1, 2);
         assert_error!("'a\n", UnclosedChar, 1, 2);
+        assert_error!("'a\r\n", UnclosedChar, 1, 2);
         assert_error!("'\\xff", UnclosedChar, 1, 5);
         assert_error!("'\\xff\n", UnclosedChar, 1, 5);
         assert_error!("'\\u007f", UnclosedChar, 1, 7);
@@ -1561,9 +2618,39 @@ Möglichkeiten""#,
         assert_error!("0E", MissingFloatExponent, 1, 2);
         assert_error!("1.2e+", MissingFloatExponent, 1, 5);
         assert_error!("1.2e-", MissingFloatExponent, 1, 5);
-        assert_error!("7e8..9.0e.", MissingFloatExponent, 1, 10);
-        assert_error!("7e8..9.0e+a", MissingFloatExponent, 1, 11);
-        assert_error!("7e8..9.0e-Z", MissingFloatExponent, 1, 11);
+        assert_error!("9.0e.", MissingFloatExponent, 1, 5);
+        assert_error!("9.0e+a", MissingFloatExponent, 1, 6);
+        assert_error!("9.0e-Z", MissingFloatExponent, 1, 6);
+    }
+
+    #[test]
+    fn error_malformed_float() {
+        assert_error!("1.2.3", MalformedFloat, 1, 4);
+        assert_error!("1e5.2", MalformedFloat, 1, 4);
+        assert_error!("1e5e2", MalformedFloat, 1, 4);
+        assert_error!("0...e2", MalformedFloat, 1, 3);
+    }
+
+    #[test]
+    fn error_empty_hex() {
+        assert_error!("0x", EmptyHex, 1, 2);
+        assert_error!("0X;", EmptyHex, 1, 3);
+        assert_error!("0xg", EmptyHex, 1, 3);
+    }
+
+    #[test]
+    fn error_integer_overflow() {
+        assert_error!("a = 9223372036854775808", IntegerOverflow, 1, 5);
+        assert_error!("0x10000000000000000", IntegerOverflow, 1, 1);
+        assert_error!("02000000000000000000000", IntegerOverflow, 1, 1);
+    }
+
+    #[test]
+    fn error_ident_after_number() {
+        assert_error!("3abc", IdentAfterNumber, 1, 2);
+        assert_error!("0x1Fg", IdentAfterNumber, 1, 5);
+        assert_error!("1.5_", IdentAfterNumber, 1, 4);
+        assert_error!("1.tostring()", IdentAfterNumber, 1, 3);
     }
 
     #[test]
@@ -1584,4 +2671,120 @@ Möglichkeiten""#,
         assert_error!("@\"\n\"\"", UnclosedVerbatimString, 2, 2);
         assert_error!("@\"\nviele Möglichkeiten", UnclosedVerbatimString, 2, 19);
     }
+
+    #[test]
+    fn byte_spans() {
+        assert_spans!("local x", 0..5, 5..6, 6..7);
+        assert_spans!("a\nb\r\nc\rd", 0..1, 1..2, 2..3, 3..5, 5..6, 6..7, 7..8);
+        assert_spans!("\"Möglichkeit\"", 0..14);
+        assert_spans!("/* a\r\nb */ @\"\n\"", 0..10, 10..11, 11..15);
+    }
+
+    #[test]
+    fn error_byte_spans() {
+        let error = Lexer::new("x = \"abc\n")
+            .collect::<Result<Vec<Token>, LexerError>>()
+            .unwrap_err();
+        assert_eq!((error.start_byte, error.end_byte), (4, 8));
+
+        let error = Lexer::new("/*")
+            .collect::<Result<Vec<Token>, LexerError>>()
+            .unwrap_err();
+        assert_eq!((error.start_byte, error.end_byte), (0, 2));
+    }
+
+    fn recover(source: &str) -> Vec<Result<Token<'_>, LexerError>> {
+        Lexer::recovering(source)
+            .map(|token| token.map(without_span))
+            .map(|error| {
+                error.map_err(|error| LexerError {
+                    start_byte: 0,
+                    end_byte: 0,
+                    ..error
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recover_unclosed_string() {
+        assert_eq!(
+            recover("a = \"bc\nd"),
+            vec![
+                Ok(token(Ident("a".into()), (1, 1), (1, 1))),
+                Ok(token(Whitespace(" ".into()), (1, 2), (1, 2))),
+                Ok(token(Eq, (1, 3), (1, 3))),
+                Ok(token(Whitespace(" ".into()), (1, 4), (1, 4))),
+                Err(error(UnclosedString, 1, 7)),
+                Ok(token(Error("\"bc".into()), (1, 5), (1, 7))),
+                Ok(token(Newline(Lf), (1, 8), (1, 8))),
+                Ok(token(Ident("d".into()), (2, 1), (2, 1))),
+            ]
+        );
+    }
+
+    #[test]
+    fn recover_inside_literals() {
+        assert_eq!(
+            recover("\"a\\qb\\\"\" 'xy' 1"),
+            vec![
+                Err(error(InvalidEscape, 1, 4)),
+                Ok(token(Error("\"a\\qb\\\"\"".into()), (1, 1), (1, 8))),
+                Ok(token(Whitespace(" ".into()), (1, 9), (1, 9))),
+                Err(error(CharTooLong, 1, 12)),
+                Ok(token(Error("'xy'".into()), (1, 10), (1, 13))),
+                Ok(token(Whitespace(" ".into()), (1, 14), (1, 14))),
+                Ok(token(int_lit("1", 1), (1, 15), (1, 15))),
+            ]
+        );
+    }
+
+    #[test]
+    fn recover_numbers_and_symbols() {
+        assert_eq!(
+            recover("0789x+$ö..b"),
+            vec![
+                Err(error(InvalidOctal, 1, 3)),
+                Ok(token(Error("0789x".into()), (1, 1), (1, 5))),
+                Ok(token(Plus, (1, 6), (1, 6))),
+                Err(error(UnexpectedSymbol, 1, 7)),
+                Ok(token(Error("$".into()), (1, 7), (1, 7))),
+                Err(error(UnexpectedSymbol, 1, 8)),
+                Ok(token(Error("ö".into()), (1, 8), (1, 8))),
+                Err(error(DoubleDot, 1, 10)),
+                Ok(token(Error("..".into()), (1, 9), (1, 10))),
+                Ok(token(Ident("b".into()), (1, 11), (1, 11))),
+            ]
+        );
+
+        assert_eq!(
+            recover("1.2.3a 3abc"),
+            vec![
+                Err(error(MalformedFloat, 1, 4)),
+                Ok(token(Error("1.2.3a".into()), (1, 1), (1, 6))),
+                Ok(token(Whitespace(" ".into()), (1, 7), (1, 7))),
+                Err(error(IdentAfterNumber, 1, 9)),
+                Ok(token(Error("3abc".into()), (1, 8), (1, 11))),
+            ]
+        );
+    }
+
+    #[test]
+    fn recover_to_end() {
+        assert_eq!(
+            recover("x /* a\nb"),
+            vec![
+                Ok(token(Ident("x".into()), (1, 1), (1, 1))),
+                Ok(token(Whitespace(" ".into()), (1, 2), (1, 2))),
+                Err(error(UnclosedMultiLineComment, 2, 1)),
+                Ok(token(Error("/* a\nb".into()), (1, 3), (2, 1))),
+            ]
+        );
+
+        let spans = Lexer::recovering("x = 'a")
+            .map(|token| token.map(|token| token.start_byte..token.end_byte))
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec![0..1, 1..2, 2..3, 3..4, 4..6]);
+    }
 }
@@ -1 +1,382 @@
+//! A formatter for Squirrel scripts.
+//!
+//! [`format_str`], [`format_bytes`] and [`format_file`] are all it takes to format scripts from
+//! another program:
+//!
+//! ```
+//! use squirrelfmt::config::Config;
+//!
+//! let config = Config::builder().indent_width(2).build();
+//! let formatted = squirrelfmt::format_str("if(a){b()}", &config).unwrap();
+//! assert_eq!(formatted, "if (a) {\n  b()\n}\n");
+//! ```
+//!
+//! The modules give access to each step on its own, e.g. to lex scripts or to format parts of them.
+
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::ast::Chunk;
+use crate::config::{Config, Dialect};
+use crate::diagnostics::Diagnostic;
+use crate::encoding::EncodingError;
+use crate::lexer::{Lexer, LexerError};
+use crate::parser::{ParseError, Parser};
+use crate::verify::{Divergence, Unstable};
+
+pub mod ast;
+pub mod config;
+pub mod diagnostics;
+pub mod encoding;
+pub mod format;
 pub mod lexer;
+pub mod line_index;
+pub mod lsp;
+pub mod parser;
+pub mod verify;
+
+/// Formats a script. The output always ends with a newline, unless it is empty.
+///
+/// The output is checked to have the same tokens as the source, see [`verify::check_tokens`].
+pub fn format_str(source: &str, config: &Config) -> Result<String, FormatError> {
+    format_str_with(source, config, &FormatOptions::default())
+}
+
+/// Like [`format_str`], but only formats part of the script or checks the output further,
+/// depending on the options.
+pub fn format_str_with(
+    source: &str,
+    config: &Config,
+    options: &FormatOptions,
+) -> Result<String, FormatError> {
+    let chunk = parse(source, config.dialect)?;
+    let formatted = match options.lines.clone() {
+        Some(lines) => format::format_range(&chunk, config, lines),
+        None => format::format(&chunk, config),
+    };
+    verify::check_tokens(source, &formatted)?;
+    if options.verify_idempotent && options.lines.is_none() {
+        verify::check_idempotent(&formatted, config).map_err(|error| match error {
+            FormatError::Unstable(unstable) => FormatError::Unstable(unstable),
+            error => FormatError::InvalidOutput {
+                output: formatted.clone(),
+                error: Box::new(error),
+            },
+        })?;
+    }
+    Ok(formatted)
+}
+
+/// Formats the bytes of a script in the encoding of the configuration, see [`encoding::decode`].
+/// The output keeps the encoding and the byte order mark of the source.
+pub fn format_bytes(source: &[u8], config: &Config) -> Result<Vec<u8>, FormatError> {
+    format_bytes_with(source, config, &FormatOptions::default())
+}
+
+/// Like [`format_bytes`], with options like [`format_str_with`].
+pub fn format_bytes_with(
+    source: &[u8],
+    config: &Config,
+    options: &FormatOptions,
+) -> Result<Vec<u8>, FormatError> {
+    let decoded = encoding::decode(source, config.encoding)?;
+    let formatted = format_str_with(&decoded.text, config, options)?;
+    Ok(decoded.encode(&formatted)?)
+}
+
+/// Formats a script file in place, and returns whether it changed. Files which are already
+/// formatted aren't written to.
+pub fn format_file(path: &Path, config: &Config) -> Result<bool, FormatError> {
+    let source = std::fs::read(path)?;
+    let formatted = format_bytes(&source, config)?;
+    if formatted == source {
+        return Ok(false);
+    }
+    std::fs::write(path, formatted)?;
+    Ok(true)
+}
+
+/// How much of a script to format, and how thoroughly to check the output. The default formats the
+/// whole script, and only checks that its tokens don't change.
+///
+/// ```
+/// use squirrelfmt::FormatOptions;
+/// use squirrelfmt::config::Config;
+///
+/// let options = FormatOptions::default().lines(2..=2);
+/// let formatted = squirrelfmt::format_str_with("a=1\nb=2\n", &Config::default(), &options);
+/// assert_eq!(formatted.unwrap(), "a=1\nb = 2\n");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct FormatOptions {
+    /// Only format the statements covering these lines, 1-based and inclusive, leaving the rest of
+    /// the script as it is. See [`format::format_range`].
+    pub lines: Option<RangeInclusive<u32>>,
+    /// Format the output a second time, and fail with [`FormatError::Unstable`] if that changes it.
+    /// Only whole scripts are checked, since the rest of a script formatted by `lines` would change.
+    pub verify_idempotent: bool,
+}
+
+impl FormatOptions {
+    pub fn lines(mut self, lines: RangeInclusive<u32>) -> Self {
+        self.lines = Some(lines);
+        self
+    }
+
+    pub fn verify_idempotent(mut self, verify_idempotent: bool) -> Self {
+        self.verify_idempotent = verify_idempotent;
+        self
+    }
+}
+
+/// Lexes and parses a script written in a dialect. Lexing goes on after errors, so that all of them
+/// are returned at once.
+pub fn parse(source: &str, dialect: Dialect) -> Result<Chunk<'_>, SyntaxError> {
+    let lexer = Lexer::recovering(source).with_dialect(dialect);
+    let (tokens, errors): (Vec<_>, Vec<_>) = lexer.partition(Result::is_ok);
+    if !errors.is_empty() {
+        let errors = errors.into_iter().filter_map(Result::err);
+        return Err(SyntaxError::Lexer(errors.collect()));
+    }
+    let tokens = tokens.into_iter().map(Result::unwrap);
+    Ok(Parser::new(tokens).with_dialect(dialect).parse()?)
+}
+
+/// Why a script couldn't be parsed, the part of [`FormatError`] that [`parse`] can return.
+#[derive(Debug)]
+pub enum SyntaxError {
+    /// The script has invalid tokens. There is at least one error, in the order of the source.
+    Lexer(Vec<LexerError>),
+    /// The script has no invalid tokens, but isn't valid Squirrel.
+    Parse(ParseError),
+}
+
+impl From<ParseError> for SyntaxError {
+    fn from(error: ParseError) -> Self {
+        SyntaxError::Parse(error)
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyntaxError::Lexer(errors) => fmt_lexer_errors(errors, f),
+            SyntaxError::Parse(error) => error.fmt(f),
+        }
+    }
+}
+
+// The first lexer error, and how many more there are
+fn fmt_lexer_errors(errors: &[LexerError], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match errors {
+        [error] => write!(f, "{error}"),
+        [error, rest @ ..] => write!(f, "{error} (and {} more)", rest.len()),
+        [] => f.write_str("invalid tokens"),
+    }
+}
+
+impl std::error::Error for SyntaxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SyntaxError::Lexer(errors) => errors.first().map(|error| error as _),
+            SyntaxError::Parse(error) => Some(error),
+        }
+    }
+}
+
+impl From<SyntaxError> for FormatError {
+    fn from(error: SyntaxError) -> Self {
+        match error {
+            SyntaxError::Lexer(errors) => FormatError::Lexer(errors),
+            SyntaxError::Parse(error) => FormatError::Parse(error),
+        }
+    }
+}
+
+/// Why a script couldn't be formatted.
+#[derive(Debug)]
+pub enum FormatError {
+    /// A script file couldn't be read or written.
+    Io(io::Error),
+    /// The script isn't valid in its encoding, or the formatted script can't be encoded in it.
+    Encoding(EncodingError),
+    /// The script has invalid tokens. There is at least one error, in the order of the source.
+    Lexer(Vec<LexerError>),
+    /// The script has no invalid tokens, but isn't valid Squirrel.
+    Parse(ParseError),
+    /// Formatting would have changed the tokens of the script, because of a bug in the formatter.
+    Diverged(Divergence),
+    /// Formatting the output again changed it, because of a bug in the formatter. Only returned by
+    /// [`verify::check_idempotent`], and when verifying idempotence with [`FormatOptions`].
+    Unstable(Unstable),
+    /// The output couldn't be formatted again, e.g. because it doesn't parse, because of a bug in
+    /// the formatter. Only returned when verifying idempotence with [`FormatOptions`]. The error is
+    /// positioned in the output.
+    InvalidOutput {
+        output: String,
+        error: Box<FormatError>,
+    },
+}
+
+impl FormatError {
+    /// The errors with their positions in the script, or in the output for invalid output, ready to
+    /// be shown to a user. There are none for IO errors and unstable output.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            FormatError::Io(_) | FormatError::Unstable(_) => Vec::new(),
+            FormatError::Encoding(error) => vec![Diagnostic::from(error)],
+            FormatError::Lexer(errors) => errors.iter().map(Diagnostic::from).collect(),
+            FormatError::Parse(error) => vec![Diagnostic::from(error)],
+            FormatError::Diverged(divergence) => vec![divergence.diagnostic()],
+            FormatError::InvalidOutput { error, .. } => error.diagnostics(),
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(error) => error.fmt(f),
+            FormatError::Encoding(error) => error.fmt(f),
+            FormatError::Lexer(errors) => fmt_lexer_errors(errors, f),
+            FormatError::Parse(error) => error.fmt(f),
+            FormatError::Diverged(divergence) => divergence.fmt(f),
+            FormatError::Unstable(unstable) => unstable.fmt(f),
+            FormatError::InvalidOutput { error, .. } => {
+                write!(f, "formatting the output again failed: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FormatError::Io(error) => Some(error),
+            FormatError::Encoding(error) => Some(error),
+            FormatError::Lexer(errors) => errors.first().map(|error| error as _),
+            FormatError::Parse(error) => Some(error),
+            FormatError::Diverged(divergence) => Some(divergence),
+            FormatError::Unstable(unstable) => Some(unstable),
+            FormatError::InvalidOutput { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for FormatError {
+    fn from(error: io::Error) -> Self {
+        FormatError::Io(error)
+    }
+}
+
+impl From<EncodingError> for FormatError {
+    fn from(error: EncodingError) -> Self {
+        FormatError::Encoding(error)
+    }
+}
+
+impl From<ParseError> for FormatError {
+    fn from(error: ParseError) -> Self {
+        FormatError::Parse(error)
+    }
+}
+
+impl From<Divergence> for FormatError {
+    fn from(divergence: Divergence) -> Self {
+        FormatError::Diverged(divergence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::lexer::LexerErrorKind;
+    use crate::parser::ParseErrorKind;
+
+    #[test]
+    fn errors() {
+        let error = format_str("a = 0080 + $", &Config::default()).unwrap_err();
+        let FormatError::Lexer(errors) = &error else {
+            panic!("expected lexer errors, got {error:?}");
+        };
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &LexerErrorKind::InvalidOctal,
+                &LexerErrorKind::UnexpectedSymbol
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid digit in octal number at 1:7 (and 1 more)"
+        );
+        let positions: Vec<_> = error
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column))
+            .collect();
+        assert_eq!(positions, [(1, 7), (1, 12)]);
+
+        let error = format_str("local = 1", &Config::default()).unwrap_err();
+        assert!(matches!(
+            error,
+            FormatError::Parse(ParseError {
+                kind: ParseErrorKind::ExpectedIdent,
+                line: 1,
+                column: 7
+            })
+        ));
+    }
+
+    #[test]
+    fn files() {
+        let path = std::env::temp_dir().join(format!("squirrelfmt-lib-{}.nut", std::process::id()));
+        std::fs::write(&path, "x=1").unwrap();
+        assert!(format_file(&path, &Config::default()).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "x = 1\n");
+        assert!(!format_file(&path, &Config::default()).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            format_file(&path, &Config::default()),
+            Err(FormatError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn options() {
+        let config = Config::default();
+        let options = FormatOptions::default().verify_idempotent(true);
+        assert_eq!(
+            format_str_with("a=1\nb=2", &config, &options).unwrap(),
+            "a = 1\nb = 2\n"
+        );
+        let options = options.lines(1..=1);
+        assert_eq!(
+            format_bytes_with(b"a=1\nb=2", &config, &options).unwrap(),
+            b"a = 1\nb=2"
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        let config = Config::default();
+        assert_eq!(
+            format_bytes(b"\xEF\xBB\xBFx=\"\xC3\xA4\"", &config).unwrap(),
+            b"\xEF\xBB\xBFx = \"\xC3\xA4\"\n"
+        );
+        let error = format_bytes(b"x=\"\xE4\"", &config).unwrap_err();
+        assert_eq!(error.to_string(), "invalid UTF-8 at 1:4");
+
+        let config = Config::builder().encoding(Encoding::Windows1252).build();
+        assert_eq!(
+            format_bytes(b"x=\"\xE4\x80\"", &config).unwrap(),
+            b"x = \"\xE4\x80\"\n"
+        );
+    }
+}
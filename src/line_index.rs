@@ -0,0 +1,183 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Converts between byte offsets into a source and line/column positions.
+///
+/// Lines and columns are 1-based like the ones on [`Token`](crate::lexer::Token). Columns are
+/// counted either in graphemes, like the lexer does, or in UTF-16 code units, like most editors do.
+/// Lines end at `\n`, `\r\n` or a lone `\r`. Positions past the end of a line or the source are
+/// clamped to it.
+#[derive(Debug)]
+pub struct LineIndex<'a> {
+    source: &'a str,
+    // The byte offset each line starts at
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let bytes = source.as_bytes();
+        let mut line_starts = vec![0];
+        for (index, byte) in bytes.iter().enumerate() {
+            match byte {
+                b'\n' => line_starts.push(index + 1),
+                b'\r' if bytes.get(index + 1) != Some(&b'\n') => line_starts.push(index + 1),
+                _ => {}
+            }
+        }
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    /// The number of lines in the source. A line ending at the very end starts an empty last line.
+    pub fn line_count(&self) -> u32 {
+        self.line_starts.len() as u32
+    }
+
+    /// The text of a line, without its line ending.
+    pub fn line_text(&self, line: u32) -> &'a str {
+        self.line(line).1
+    }
+
+    /// The line and grapheme column of a byte offset. An offset inside a grapheme belongs to it.
+    pub fn line_column(&self, offset: usize) -> (u32, u32) {
+        let (line, prefix) = self.prefix(offset);
+        let (_, text) = self.line(line);
+        let graphemes = text
+            .grapheme_indices(true)
+            .take_while(|(index, grapheme)| index + grapheme.len() <= prefix.len())
+            .count();
+        (line, graphemes as u32 + 1)
+    }
+
+    /// The line and UTF-16 column of a byte offset. An offset inside a character belongs to it.
+    pub fn line_utf16_column(&self, offset: usize) -> (u32, u32) {
+        let (line, prefix) = self.prefix(offset);
+        (line, prefix.encode_utf16().count() as u32 + 1)
+    }
+
+    /// The byte offset of a line and grapheme column.
+    pub fn offset(&self, line: u32, column: u32) -> usize {
+        let (start, text) = self.line(line);
+        let skip = column.saturating_sub(1) as usize;
+        match text.grapheme_indices(true).nth(skip) {
+            Some((index, _)) => start + index,
+            None => start + text.len(),
+        }
+    }
+
+    /// The byte offset of a line and UTF-16 column. A column inside a surrogate pair is moved to
+    /// the start of its character.
+    pub fn utf16_offset(&self, line: u32, column: u32) -> usize {
+        let (start, text) = self.line(line);
+        let mut remaining = column.saturating_sub(1) as usize;
+        for (index, char) in text.char_indices() {
+            if remaining < char.len_utf16() {
+                return start + index;
+            }
+            remaining -= char.len_utf16();
+        }
+        start + text.len()
+    }
+
+    /// Converts a grapheme column on a line to a UTF-16 column.
+    pub fn to_utf16_column(&self, line: u32, column: u32) -> u32 {
+        self.line_utf16_column(self.offset(line, column)).1
+    }
+
+    /// Converts a UTF-16 column on a line to a grapheme column.
+    pub fn from_utf16_column(&self, line: u32, column: u32) -> u32 {
+        self.line_column(self.utf16_offset(line, column)).1
+    }
+
+    // The line an offset is on and the text of that line before it
+    fn prefix(&self, offset: usize) -> (u32, &'a str) {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let (start, text) = self.line(line as u32);
+        // The offset may point into the line ending, which isn't part of the text
+        let end = (offset - start).min(text.len());
+        (line as u32, &text[..end])
+    }
+
+    // The start offset and text of a line, without its line ending
+    fn line(&self, line: u32) -> (usize, &'a str) {
+        let index = line.max(1) as usize - 1;
+        if index >= self.line_starts.len() {
+            return (self.source.len(), "");
+        }
+        let start = self.line_starts[index];
+        let end = self
+            .line_starts
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.source.len());
+        let text = self.source[start..end].trim_end_matches(['\n', '\r']);
+        (start, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let index = LineIndex::new("a\nbc\r\nd\re");
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line_column(0), (1, 1));
+        assert_eq!(index.line_column(1), (1, 2));
+        assert_eq!(index.line_column(2), (2, 1));
+        assert_eq!(index.line_column(4), (2, 3));
+        assert_eq!(index.line_column(5), (2, 3));
+        assert_eq!(index.line_column(6), (3, 1));
+        assert_eq!(index.line_column(8), (4, 1));
+        assert_eq!(index.offset(2, 2), 3);
+        assert_eq!(index.offset(4, 1), 8);
+
+        assert_eq!(LineIndex::new("a\n").line_count(), 2);
+        assert_eq!(LineIndex::new("").line_column(0), (1, 1));
+    }
+
+    #[test]
+    fn graphemes() {
+        // "é" as an "e" followed by a combining accent is one grapheme of three bytes
+        let index = LineIndex::new("e\u{301}x = \"Möglichkeit\"");
+        assert_eq!(index.line_column(3), (1, 2));
+        assert_eq!(index.line_column(1), (1, 1));
+        assert_eq!(index.offset(1, 2), 3);
+        assert_eq!(index.offset(1, 8), 9);
+        assert_eq!(index.offset(1, 9), 11);
+        assert_eq!(index.line_column(10), (1, 8));
+        assert_eq!(index.line_column(11), (1, 9));
+    }
+
+    #[test]
+    fn utf16() {
+        // "😀" is one grapheme, four bytes and two UTF-16 code units
+        let index = LineIndex::new("x\n\"😀\" + e\u{301}");
+        assert_eq!(index.line_utf16_column(7), (2, 4));
+        assert_eq!(index.line_column(7), (2, 3));
+        assert_eq!(index.utf16_offset(2, 4), 7);
+        assert_eq!(index.utf16_offset(2, 3), 3);
+        assert_eq!(index.to_utf16_column(2, 3), 4);
+        assert_eq!(index.from_utf16_column(2, 4), 3);
+        assert_eq!(index.to_utf16_column(2, 7), 8);
+        assert_eq!(index.from_utf16_column(2, 9), 7);
+    }
+
+    #[test]
+    fn clamped() {
+        let index = LineIndex::new("ab\ncd");
+        assert_eq!(index.offset(1, 10), 2);
+        assert_eq!(index.offset(10, 1), 5);
+        assert_eq!(index.offset(0, 0), 0);
+        assert_eq!(index.line_column(100), (2, 3));
+        assert_eq!(index.utf16_offset(1, 10), 2);
+    }
+}
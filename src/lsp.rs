@@ -0,0 +1,450 @@
+//! A language server speaking LSP over stdio, run by `squirrelfmt lsp`.
+//!
+//! It formats whole documents and ranges of lines, and publishes lexer and parse errors as
+//! diagnostics whenever a document is opened or changed. Documents are formatted with the
+//! `squirrelfmt.toml` applying to their file, like the command line does. The formatting options
+//! sent by editors are ignored, so that the output doesn't depend on who formats a script.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationType, PublishDiagnostics,
+};
+use lsp_types::request::{Formatting, RangeFormatting, Request as RequestType};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DocumentFormattingParams, DocumentRangeFormattingParams, OneOf,
+    Position, PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Uri,
+};
+
+use crate::SyntaxError;
+use crate::ast::Chunk;
+use crate::config::{Config, ConfigError, Dialect};
+use crate::diagnostics;
+use crate::format::{format, format_range};
+use crate::line_index::LineIndex;
+use crate::verify::check_tokens;
+
+/// Serves a client over stdin and stdout until it exits.
+pub fn run() -> io::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(&connection)?;
+    // The connection has to be dropped for the writer thread to finish
+    drop(connection);
+    io_threads.join()
+}
+
+fn serve(connection: &Connection) -> io::Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    let capabilities = serde_json::to_value(capabilities).expect("capabilities are valid JSON");
+    connection
+        .initialize(capabilities)
+        .map_err(io::Error::other)?;
+
+    let mut server = Server {
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        let reply = match message {
+            Message::Request(request) => {
+                if connection
+                    .handle_shutdown(&request)
+                    .map_err(io::Error::other)?
+                {
+                    return Ok(());
+                }
+                Message::Response(server.request(request))
+            }
+            Message::Notification(notification) => match server.notification(notification) {
+                Some(notification) => Message::Notification(notification),
+                None => continue,
+            },
+            Message::Response(_) => continue,
+        };
+        connection.sender.send(reply).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+struct Server {
+    // The text of the open documents, as last sent by the client
+    documents: HashMap<Uri, String>,
+}
+
+impl Server {
+    fn request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            Formatting::METHOD => request
+                .extract::<DocumentFormattingParams>(Formatting::METHOD)
+                .map(|(_, params)| self.format(&params.text_document.uri, None)),
+            RangeFormatting::METHOD => request
+                .extract::<DocumentRangeFormattingParams>(RangeFormatting::METHOD)
+                .map(|(_, params)| self.format(&params.text_document.uri, Some(params.range))),
+            method => {
+                let message = format!("unsupported request `{method}`");
+                return Response::new_err(id, ErrorCode::MethodNotFound as i32, message);
+            }
+        };
+        match result {
+            Ok(Ok(edits)) => Response::new_ok(id, edits),
+            Ok(Err(message)) => Response::new_err(id, ErrorCode::RequestFailed as i32, message),
+            Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error.to_string()),
+        }
+    }
+
+    // Keeps track of the open documents. Returns the diagnostics to publish for a document that was
+    // opened, changed or closed.
+    fn notification(&mut self, notification: Notification) -> Option<Notification> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                    .ok()?;
+                let document = params.text_document;
+                self.documents.insert(document.uri.clone(), document.text);
+                document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidChangeTextDocumentParams>(
+                        DidChangeTextDocument::METHOD,
+                    )
+                    .ok()?;
+                // Changes are always the full text, as announced in the capabilities
+                let text = params.content_changes.into_iter().last()?.text;
+                self.documents
+                    .insert(params.text_document.uri.clone(), text);
+                params.text_document.uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                    .ok()?;
+                self.documents.remove(&params.text_document.uri);
+                params.text_document.uri
+            }
+            _ => return None,
+        };
+
+        let diagnostics = match self.documents.get(&uri) {
+            // A broken configuration file is reported when formatting
+            Some(source) => {
+                let dialect = config(&uri).unwrap_or_default().dialect;
+                parse(source, dialect).err().unwrap_or_default()
+            }
+            None => Vec::new(),
+        };
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        Some(Notification::new(
+            PublishDiagnostics::METHOD.to_owned(),
+            params,
+        ))
+    }
+
+    // The edits formatting a document, or the lines covered by `range`. Documents with syntax errors
+    // aren't formatted, as the errors are already published as diagnostics.
+    fn format(&self, uri: &Uri, range: Option<Range>) -> Result<Option<Vec<TextEdit>>, String> {
+        let source = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| format!("unknown document `{}`", uri.as_str()))?;
+        let config = config(uri).map_err(|error| error.to_string())?;
+        let Ok(chunk) = parse(source, config.dialect) else {
+            return Ok(None);
+        };
+
+        let formatted = match range {
+            Some(range) => {
+                // A range ending at the start of a line doesn't include that line
+                let end = match range.end {
+                    Position { line, character: 0 } if line > range.start.line => line,
+                    end => end.line + 1,
+                };
+                format_range(&chunk, &config, range.start.line + 1..=end)
+            }
+            None => format(&chunk, &config),
+        };
+        check_tokens(source, &formatted).map_err(|divergence| divergence.to_string())?;
+        Ok(Some(edit(source, &formatted).into_iter().collect()))
+    }
+}
+
+// Lexes and parses a document, or returns diagnostics for all lexer errors, or else the parse
+// error.
+fn parse(source: &str, dialect: Dialect) -> Result<Chunk<'_>, Vec<Diagnostic>> {
+    let index = LineIndex::new(source);
+    crate::parse(source, dialect).map_err(|error| match error {
+        SyntaxError::Lexer(errors) => errors
+            .iter()
+            .map(|error| {
+                // From the start of the token to past the character the error was detected at
+                let end = index.offset(error.line, error.column + 1);
+                let range = Range::new(
+                    position(&index, error.start_byte),
+                    position(&index, end.max(error.end_byte)),
+                );
+                diagnostic(range, diagnostics::Diagnostic::from(error))
+            })
+            .collect(),
+        SyntaxError::Parse(error) => {
+            let start = position(&index, index.offset(error.line, error.column));
+            let range = Range::new(start, start);
+            vec![diagnostic(range, diagnostics::Diagnostic::from(&error))]
+        }
+    })
+}
+
+fn diagnostic(range: Range, error: diagnostics::Diagnostic) -> Diagnostic {
+    let message = match error.help {
+        Some(help) => format!("{}\nhelp: {help}", error.message),
+        None => error.message,
+    };
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("squirrelfmt".to_owned()),
+        message,
+        ..Diagnostic::default()
+    }
+}
+
+// An edit replacing the lines which differ between a document and its formatted text, so that
+// editors don't move the cursor or folds around elsewhere.
+fn edit(source: &str, formatted: &str) -> Option<TextEdit> {
+    if source == formatted {
+        return None;
+    }
+    let common_prefix = source
+        .bytes()
+        .zip(formatted.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let common_suffix = source
+        .bytes()
+        .rev()
+        .zip(formatted.bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+        .min(source.len().min(formatted.len()) - common_prefix);
+
+    // Whole lines are replaced, which also keeps `\r\n` from being split
+    let prefix = source[..common_prefix]
+        .rfind('\n')
+        .map_or(0, |index| index + 1);
+    let suffix = source[source.len() - common_suffix..]
+        .find('\n')
+        .map_or(0, |index| common_suffix - index - 1);
+
+    let index = LineIndex::new(source);
+    let range = Range::new(
+        position(&index, prefix),
+        position(&index, source.len() - suffix),
+    );
+    let new_text = formatted[prefix..formatted.len() - suffix].to_owned();
+    Some(TextEdit::new(range, new_text))
+}
+
+// The LSP position of a byte offset, with the line and the column in UTF-16 code units counted
+// from 0
+fn position(index: &LineIndex, offset: usize) -> Position {
+    let (line, column) = index.line_utf16_column(offset);
+    Position::new(line - 1, column - 1)
+}
+
+// The configuration applying to a document, or the default one if it isn't a file
+fn config(uri: &Uri) -> Result<Config, ConfigError> {
+    match file_path(uri) {
+        Some(path) => Config::for_path(&path),
+        None => Ok(Config::default()),
+    }
+}
+
+// The path of a `file:` URI
+fn file_path(uri: &Uri) -> Option<PathBuf> {
+    if !uri.scheme()?.as_str().eq_ignore_ascii_case("file") {
+        return None;
+    }
+    let path = uri.path().as_estr().decode().into_string().ok()?;
+    // Windows paths look like `/C:/scripts/main.nut`
+    if cfg!(windows) {
+        return Some(PathBuf::from(path.strip_prefix('/').unwrap_or(&path)));
+    }
+    Some(PathBuf::from(path.into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(text: &str) -> Uri {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn edits() {
+        assert_eq!(edit("a = 1\n", "a = 1\n"), None);
+        let edit = |source, formatted| {
+            let edit = edit(source, formatted).unwrap();
+            let (start, end) = (edit.range.start, edit.range.end);
+            (
+                (start.line, start.character),
+                (end.line, end.character),
+                edit.new_text,
+            )
+        };
+        assert_eq!(
+            edit("a = 1\nb=2\nc = 3\n", "a = 1\nb = 2\nc = 3\n"),
+            ((1, 0), (2, 0), "b = 2\n".to_owned())
+        );
+        assert_eq!(
+            edit("a = 1\r\nb=2\r\n", "a = 1\r\nb = 2\r\n"),
+            ((1, 0), (2, 0), "b = 2\r\n".to_owned())
+        );
+        assert_eq!(
+            edit("x=1", "x = 1\n"),
+            ((0, 0), (0, 3), "x = 1\n".to_owned())
+        );
+        // Lines removed at the end, where the formatted text is a prefix of the source
+        assert_eq!(
+            edit("a = 1\n\n\n", "a = 1\n"),
+            ((1, 0), (3, 0), String::new())
+        );
+    }
+
+    #[test]
+    fn diagnostics() {
+        // "😀" takes two UTF-16 code units
+        let diagnostics = parse("local s = \"😀\" $ 0080", Dialect::Squirrel3).unwrap_err();
+        let ranges: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.range)
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                Range::new(Position::new(0, 15), Position::new(0, 16)),
+                Range::new(Position::new(0, 17), Position::new(0, 20)),
+            ]
+        );
+        assert_eq!(diagnostics[0].message, "unexpected character");
+        assert!(
+            diagnostics[1]
+                .message
+                .starts_with("invalid digit in octal number\nhelp: ")
+        );
+
+        let diagnostics = parse("\"😀\"\nlocal = 1", Dialect::Squirrel3).unwrap_err();
+        assert_eq!(diagnostics[0].message, "expected an identifier");
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 6), Position::new(1, 6))
+        );
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            file_path(&uri("file:///home/me/my%20scripts/main.nut")),
+            Some(PathBuf::from("/home/me/my scripts/main.nut"))
+        );
+        assert_eq!(file_path(&uri("untitled:Untitled-1")), None);
+    }
+
+    #[test]
+    fn session() {
+        let (server, client) = Connection::memory();
+        let thread = std::thread::spawn(move || serve(&server));
+        let request = |id: i32, method: &str, params: serde_json::Value| {
+            let request = Request::new(id.into(), method.to_owned(), params);
+            client.sender.send(request.into()).unwrap();
+            match client.receiver.recv().unwrap() {
+                Message::Response(response) => response,
+                message => panic!("expected a response, got {message:?}"),
+            }
+        };
+        let notify = |method: &str, params: serde_json::Value| {
+            let notification = Notification::new(method.to_owned(), params);
+            client.sender.send(notification.into()).unwrap();
+        };
+        let published = || match client.receiver.recv().unwrap() {
+            Message::Notification(notification) => {
+                notification
+                    .extract::<PublishDiagnosticsParams>(PublishDiagnostics::METHOD)
+                    .unwrap()
+                    .diagnostics
+            }
+            message => panic!("expected diagnostics, got {message:?}"),
+        };
+
+        let response = request(1, "initialize", serde_json::json!({ "capabilities": {} }));
+        assert!(response.result.unwrap()["capabilities"]["documentFormattingProvider"] == true);
+        notify("initialized", serde_json::json!({}));
+
+        let document = serde_json::json!({ "uri": "untitled:main.nut" });
+        notify(
+            DidOpenTextDocument::METHOD,
+            serde_json::json!({
+                "textDocument": { "uri": "untitled:main.nut", "languageId": "squirrel", "version": 1, "text": "x=\"" }
+            }),
+        );
+        assert_eq!(published().len(), 1);
+        let response = request(
+            2,
+            Formatting::METHOD,
+            serde_json::json!({
+                "textDocument": document, "options": { "tabSize": 4, "insertSpaces": true }
+            }),
+        );
+        assert_eq!(response.result, Some(serde_json::Value::Null));
+
+        notify(
+            DidChangeTextDocument::METHOD,
+            serde_json::json!({
+                "textDocument": { "uri": "untitled:main.nut", "version": 2 },
+                "contentChanges": [{ "text": "x=1\ny=2\n" }]
+            }),
+        );
+        assert_eq!(published(), []);
+        let response = request(
+            3,
+            RangeFormatting::METHOD,
+            serde_json::json!({
+                "textDocument": document,
+                "range": { "start": { "line": 1, "character": 0 }, "end": { "line": 2, "character": 0 } },
+                "options": { "tabSize": 4, "insertSpaces": true }
+            }),
+        );
+        let edits: Vec<TextEdit> = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert_eq!(
+            edits,
+            [TextEdit::new(
+                Range::new(Position::new(1, 0), Position::new(2, 0)),
+                "y = 2\n".to_owned()
+            )]
+        );
+
+        let response = request(4, "textDocument/hover", serde_json::json!({}));
+        assert_eq!(
+            response.error.unwrap().code,
+            ErrorCode::MethodNotFound as i32
+        );
+
+        request(5, "shutdown", serde_json::Value::Null);
+        notify("exit", serde_json::Value::Null);
+        thread.join().unwrap().unwrap();
+    }
+}
@@ -1 +1,353 @@
-fn main() {}
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser as _;
+use similar::TextDiff;
+use walkdir::WalkDir;
+
+use squirrelfmt::config::{self, Config, ConfigError, Dialect};
+use squirrelfmt::encoding::{self, Encoding};
+use squirrelfmt::{FormatError, FormatOptions};
+
+/// Formats Squirrel scripts in place.
+#[derive(clap::Parser)]
+#[command(
+    version,
+    after_help = "Exits with status 1 if `--check` or `--diff` found unformatted files, with status 2 \
+                  if the command line is invalid, and with status 3 if a file couldn't be formatted.",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Files or directories to format. Directories are searched recursively for `.nut` files. `-`
+    /// reads a script from stdin and writes it formatted to stdout.
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Don't write anything, only list the files which aren't formatted. Exits with status 1 if
+    /// there are any, which isn't used for anything else.
+    #[arg(long)]
+    check: bool,
+
+    /// Don't write anything, print a unified diff of what would change instead. Exits like `--check`.
+    #[arg(long)]
+    diff: bool,
+
+    /// Use this configuration file for every script, instead of the `squirrelfmt.toml` closest to
+    /// each of them.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// The version of Squirrel the scripts are written in, `2.2` or `3.2`, instead of the one in their
+    /// configuration file.
+    #[arg(long, value_name = "VERSION")]
+    dialect: Option<Dialect>,
+
+    /// The encoding of the scripts, `utf-8` or `windows-1252`, instead of the one in their
+    /// configuration file. Scripts starting with a byte order mark are always UTF-8.
+    #[arg(long, value_name = "ENCODING")]
+    encoding: Option<Encoding>,
+
+    /// Only format the statements covering these lines, e.g. `120:180`, leaving the rest of the
+    /// script as it is. Needs a single file or `-`.
+    #[arg(long, value_name = "START:END", value_parser = parse_lines)]
+    lines: Option<RangeInclusive<u32>>,
+
+    /// Format the output a second time, and fail with a diff if that changes it.
+    #[arg(long, conflicts_with = "lines")]
+    verify_idempotent: bool,
+
+    /// When to colour error messages.
+    #[arg(long, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Runs a language server over stdin and stdout, for editors to format scripts and show syntax
+    /// errors.
+    Lsp,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Color {
+    /// Only when writing to a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+// Parses a `--lines` range, 1-based and inclusive
+fn parse_lines(text: &str) -> Result<RangeInclusive<u32>, String> {
+    let (start, end) = text
+        .split_once(':')
+        .ok_or("expected a range like `120:180`")?;
+    let line = |text: &str| match text.parse::<u32>() {
+        Ok(0) => Err("lines start at 1".to_owned()),
+        Ok(line) => Ok(line),
+        Err(error) => Err(format!("invalid line `{text}`: {error}")),
+    };
+    let (start, end) = (line(start)?, line(end)?);
+    if start > end {
+        return Err("the range ends before it starts".to_owned());
+    }
+    Ok(start..=end)
+}
+
+impl Args {
+    fn writes(&self) -> bool {
+        !self.check && !self.diff
+    }
+}
+
+// The status for `--check` finding unformatted files, to tell them apart from files which couldn't
+// be formatted at all, and from an invalid command line, for which clap exits with 2
+const EXIT_UNFORMATTED: u8 = 1;
+const EXIT_ERROR: u8 = 3;
+
+enum Error {
+    Io(io::Error),
+    Config(ConfigError),
+    // A script couldn't be formatted, along with the text the error is positioned in
+    Format(FormatError, String),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+// The configuration for each script, from `--config` or the closest `squirrelfmt.toml`, with the
+// dialect from `--dialect` and the encoding from `--encoding`. Files are only read once, however
+// many scripts they apply to.
+struct Configs {
+    explicit: Option<Config>,
+    dialect: Option<Dialect>,
+    encoding: Option<Encoding>,
+    files: HashMap<PathBuf, Config>,
+}
+
+impl Configs {
+    fn for_path(&mut self, path: &Path) -> Result<Config, ConfigError> {
+        let mut config = self.read(path)?;
+        if let Some(dialect) = self.dialect {
+            config.dialect = dialect;
+        }
+        if let Some(encoding) = self.encoding {
+            config.encoding = encoding;
+        }
+        Ok(config)
+    }
+
+    fn read(&mut self, path: &Path) -> Result<Config, ConfigError> {
+        if let Some(config) = &self.explicit {
+            return Ok(config.clone());
+        }
+        let Some(file) = config::find(path) else {
+            return Ok(Config::default());
+        };
+        if let Some(config) = self.files.get(&file) {
+            return Ok(config.clone());
+        }
+        let config = Config::from_file(&file)?;
+        self.files.insert(file, config.clone());
+        Ok(config)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if let Some(Command::Lsp) = args.command {
+        return match squirrelfmt::lsp::run() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("error: {error}");
+                ExitCode::from(EXIT_ERROR)
+            }
+        };
+    }
+    let colors = match args.color {
+        Color::Auto => io::stderr().is_terminal(),
+        Color::Always => true,
+        Color::Never => false,
+    };
+    let explicit = match args.config.as_deref().map(Config::from_file).transpose() {
+        Ok(explicit) => explicit,
+        Err(error) => {
+            eprintln!("error: {error}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+    if args.lines.is_some() && (args.paths.len() > 1 || args.paths[0].is_dir()) {
+        eprintln!("error: `--lines` needs a single file");
+        return ExitCode::from(EXIT_ERROR);
+    }
+    let mut configs = Configs {
+        explicit,
+        dialect: args.dialect,
+        encoding: args.encoding,
+        files: HashMap::new(),
+    };
+    let mut failed = false;
+    let mut unformatted = false;
+
+    let mut handle = |path: &Path, result: Result<bool, Error>| match result {
+        Ok(true) => {}
+        Ok(false) => {
+            if args.check {
+                println!("{}", path.display());
+            }
+            unformatted |= !args.writes();
+        }
+        Err(error) => {
+            report(path, &error, colors);
+            failed = true;
+        }
+    };
+
+    for path in &args.paths {
+        if path.as_os_str() == "-" {
+            // Stdin gets the configuration of the working directory
+            let result = configs
+                .for_path(Path::new("."))
+                .map_err(Error::Config)
+                .and_then(|config| format_stdin(&args, &config));
+            handle(Path::new("<stdin>"), result);
+            continue;
+        }
+
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    let path = error.path().unwrap_or(path).to_owned();
+                    // Only filesystem loops aren't IO errors
+                    let message = error.to_string();
+                    let error = error
+                        .into_io_error()
+                        .unwrap_or_else(|| io::Error::other(message));
+                    handle(&path, Err(Error::Io(error)));
+                    continue;
+                }
+            };
+
+            // Files given explicitly are formatted whatever their extension
+            let is_script = entry.depth() == 0 || entry.path().extension() == Some("nut".as_ref());
+            if !entry.file_type().is_file() || !is_script {
+                continue;
+            }
+            let result = configs
+                .for_path(entry.path())
+                .map_err(Error::Config)
+                .and_then(|config| format_file(entry.path(), &args, &config));
+            handle(entry.path(), result);
+        }
+    }
+
+    if failed {
+        ExitCode::from(EXIT_ERROR)
+    } else if unformatted {
+        ExitCode::from(EXIT_UNFORMATTED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// Formats the bytes of a script with the options given on the command line
+fn format_source(source: &[u8], args: &Args, config: &Config) -> Result<Vec<u8>, Error> {
+    let mut options = FormatOptions::default().verify_idempotent(args.verify_idempotent);
+    if let Some(lines) = args.lines.clone() {
+        options = options.lines(lines);
+    }
+    squirrelfmt::format_bytes_with(source, config, &options).map_err(|error| {
+        let text = match &error {
+            FormatError::InvalidOutput { output, .. } => output.clone(),
+            _ => text(source, config),
+        };
+        Error::Format(error, text)
+    })
+}
+
+// The text of a script, to show it to a user. Only the valid part of a script which can't be
+// decoded is needed to show where the error is.
+fn text(bytes: &[u8], config: &Config) -> String {
+    match encoding::decode(bytes, config.encoding) {
+        Ok(decoded) => decoded.text.into_owned(),
+        Err(_) => {
+            let bytes = bytes.strip_prefix(encoding::BOM).unwrap_or(bytes);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+}
+
+// Formats a file in place, unless it is already formatted or `--check` or `--diff` is given.
+// Returns whether the file was already formatted.
+fn format_file(path: &Path, args: &Args, config: &Config) -> Result<bool, Error> {
+    let source = fs::read(path)?;
+    let formatted = format_source(&source, args, config)?;
+    if formatted == source {
+        return Ok(true);
+    }
+    if args.diff {
+        print_diff(path, &text(&source, config), &text(&formatted, config))?;
+    }
+    if args.writes() {
+        fs::write(path, formatted)?;
+    }
+    Ok(false)
+}
+
+// Like `format_file`, but writes the formatted script to stdout instead.
+fn format_stdin(args: &Args, config: &Config) -> Result<bool, Error> {
+    let mut source = Vec::new();
+    io::stdin().read_to_end(&mut source)?;
+    let formatted = format_source(&source, args, config)?;
+    if args.diff && formatted != source {
+        let path = Path::new("<stdin>");
+        print_diff(path, &text(&source, config), &text(&formatted, config))?;
+    }
+    if args.writes() {
+        io::stdout().write_all(&formatted)?;
+    }
+    Ok(formatted == source)
+}
+
+fn print_diff(path: &Path, source: &str, formatted: &str) -> io::Result<()> {
+    let path = path.display().to_string();
+    let diff = TextDiff::from_lines(source, formatted);
+    let mut diff = diff.unified_diff();
+    diff.header(&path, &path);
+    write!(io::stdout(), "{diff}")
+}
+
+fn report(path: &Path, error: &Error, colors: bool) {
+    let path = path.display();
+    match error {
+        Error::Io(error) => eprintln!("error: {path}: {error}"),
+        // The error names the configuration file rather than the script
+        Error::Config(error) => eprintln!("error: {error}"),
+        Error::Format(FormatError::Unstable(unstable), _) => {
+            eprint!(
+                "error: {path}: formatting is not idempotent\n{}",
+                unstable.diff()
+            );
+        }
+        Error::Format(error, text) => {
+            let diagnostics = error.diagnostics();
+            if diagnostics.is_empty() {
+                eprintln!("error: {path}: {error}");
+            }
+            for diagnostic in diagnostics {
+                eprint!("{}", diagnostic.render(&path.to_string(), text, colors));
+            }
+        }
+    }
+}
@@ -0,0 +1,1561 @@
+use std::fmt;
+
+use derive_more::Constructor;
+
+use crate::ast::*;
+use crate::config::Dialect;
+use crate::lexer::{Token, TokenKind};
+
+/// A recursive descent parser that builds a [`Chunk`] from the token stream of a `Lexer`.
+///
+/// The grammar follows the reference Squirrel 3.2 compiler (`sqcompiler.cpp`), including its
+/// newline sensitivity: a statement may end at a newline instead of a `;`, a postfix `++`/`--` at the
+/// start of a line does not continue the expression on the previous line, and a `[` at the start of
+/// a line right after an expression is an error, since it can neither index it nor start anything
+/// else without a `,` or `;` in between.
+///
+/// Whitespace, newlines and comments are attached to the significant tokens around them as trivia
+/// (see [`TokenReference`]), so the resulting tree still contains every byte of the source.
+pub struct Parser<'src> {
+    // Stored in reverse so that the next token can be popped off the end. The last element is always
+    // the `Eof` token, which is only popped once parsing has finished.
+    tokens: Vec<TokenReference<'src>>,
+    // Whether a newline appeared between each token and the significant token before it. Indexed
+    // like `tokens`.
+    newlines: Vec<bool>,
+    // Whether the last consumed token was a `}` or a `;`, after which a statement may end without a
+    // newline.
+    after_terminator: bool,
+    dialect: Dialect,
+}
+
+impl<'src> Parser<'src> {
+    pub fn new(tokens: impl IntoIterator<Item = Token<'src>>) -> Self {
+        let mut significant = Vec::new();
+        let mut leading_trivia = Vec::new();
+        let mut newline = false;
+        let mut end = (1, 1);
+        let mut end_byte = 0;
+        let mut tokens = tokens
+            .into_iter()
+            .inspect(|token| {
+                end = position_after(token);
+                end_byte = token.end_byte;
+            })
+            .peekable();
+
+        while let Some(token) = tokens.next() {
+            if token.kind.is_trivia() {
+                newline |= matches!(token.kind, TokenKind::Newline(_));
+                leading_trivia.push(token);
+                continue;
+            }
+
+            // Trailing trivia is everything up to and including the end of the line the token is on.
+            // The rest belongs to the next token as leading trivia.
+            let mut trailing_trivia = Vec::new();
+            let mut ends_line = false;
+            while !ends_line && let Some(trivia) = tokens.next_if(|next| next.kind.is_trivia()) {
+                ends_line = matches!(trivia.kind, TokenKind::Newline(_));
+                trailing_trivia.push(trivia);
+            }
+
+            significant.push((
+                TokenReference::new(std::mem::take(&mut leading_trivia), token, trailing_trivia),
+                newline,
+            ));
+            newline = ends_line;
+        }
+
+        let eof = TokenReference::new(
+            leading_trivia,
+            Token::new(
+                TokenKind::Eof,
+                end.0,
+                end.1,
+                end.0,
+                end.1,
+                end_byte,
+                end_byte,
+            ),
+            Vec::new(),
+        );
+        significant.push((eof, newline));
+
+        let (tokens, newlines) = significant.into_iter().rev().unzip();
+        Self {
+            tokens,
+            newlines,
+            after_terminator: false,
+            dialect: Dialect::Squirrel3,
+        }
+    }
+
+    /// Parses the grammar of a dialect, Squirrel 3 being the default. Syntax of newer dialects is an
+    /// error, i.e. lambdas, default parameter values, JSON-style table entries and static members
+    /// in Squirrel 2.
+    pub fn with_dialect(self, dialect: Dialect) -> Self {
+        Self { dialect, ..self }
+    }
+
+    pub fn parse(mut self) -> Result<Chunk<'src>, ParseError> {
+        let stmts = self.parse_stmts(|kind| *kind == TokenKind::Eof)?;
+        let eof = self
+            .tokens
+            .pop()
+            .expect("the Eof token should be the last token left");
+        Ok(Chunk { stmts, eof })
+    }
+
+    fn peek(&self) -> &TokenKind<'src> {
+        &self.peek_token().kind
+    }
+
+    fn peek_token(&self) -> &TokenReference<'src> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> &TokenReference<'src> {
+        let len = self.tokens.len();
+        // Looking past the end of the stream keeps returning the Eof token
+        &self.tokens[len.saturating_sub(n + 1)]
+    }
+
+    fn newline_before(&self) -> bool {
+        *self
+            .newlines
+            .last()
+            .expect("the Eof token should never be popped")
+    }
+
+    fn at(&self, kind: &TokenKind<'_>) -> bool {
+        self.peek() == kind
+    }
+
+    fn bump(&mut self) -> TokenReference<'src> {
+        debug_assert!(
+            self.tokens.len() > 1,
+            "the Eof token should never be bumped"
+        );
+        self.newlines.pop();
+        let token = self
+            .tokens
+            .pop()
+            .expect("the Eof token should never be popped");
+        self.after_terminator = matches!(token.kind, TokenKind::BraceClose | TokenKind::Semicolon);
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind<'_>) -> Option<TokenReference<'src>> {
+        if self.at(kind) {
+            Some(self.bump())
+        } else {
+            None
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind<'static>) -> Result<TokenReference<'src>, ParseError> {
+        if self.at(&kind) {
+            Ok(self.bump())
+        } else {
+            Err(self.error(ParseErrorKind::ExpectedToken(Box::new(kind))))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<TokenReference<'src>, ParseError> {
+        if let TokenKind::Ident(_) = self.peek() {
+            Ok(self.bump())
+        } else {
+            Err(self.error(ParseErrorKind::ExpectedIdent))
+        }
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        let token = self.peek_token();
+        ParseError::new(kind, token.start_line, token.start_column)
+    }
+
+    fn at_end_of_stmt(&self) -> bool {
+        self.newline_before()
+            || matches!(
+                self.peek(),
+                TokenKind::Semicolon | TokenKind::BraceClose | TokenKind::Eof
+            )
+    }
+
+    // Squirrel doesn't require a `;` after a statement if it is followed by a newline or a `}`, or
+    // if the statement itself ended with a `}`. An `if` body may also be directly followed by `else`.
+    fn expect_end_of_stmt(&self, allow_else: bool) -> Result<(), ParseError> {
+        if self.after_terminator
+            || self.at_end_of_stmt()
+            || (allow_else && self.at(&TokenKind::Else))
+        {
+            Ok(())
+        } else {
+            Err(self.error(ParseErrorKind::ExpectedEndOfStatement))
+        }
+    }
+
+    fn parse_stmts(
+        &mut self,
+        end: fn(&TokenKind<'_>) -> bool,
+    ) -> Result<Vec<Stmt<'src>>, ParseError> {
+        let mut stmts = Vec::new();
+        while !end(self.peek()) {
+            if self.at(&TokenKind::Eof) {
+                return Err(self.error(ParseErrorKind::ExpectedToken(Box::new(
+                    TokenKind::BraceClose,
+                ))));
+            }
+
+            stmts.push(self.parse_stmt()?);
+            self.expect_end_of_stmt(false)?;
+        }
+        Ok(stmts)
+    }
+
+    fn parse_stmt(&mut self) -> Result<Stmt<'src>, ParseError> {
+        let kind = match self.peek() {
+            TokenKind::Semicolon => {
+                return Ok(Stmt {
+                    kind: StmtKind::Empty,
+                    semicolon: Some(self.bump()),
+                });
+            }
+            TokenKind::BraceOpen => StmtKind::Block(self.parse_block()?),
+            TokenKind::If => self.parse_if()?,
+            TokenKind::While => self.parse_while()?,
+            TokenKind::Do => self.parse_do_while()?,
+            TokenKind::For => self.parse_for()?,
+            TokenKind::Foreach => self.parse_foreach()?,
+            TokenKind::Switch => self.parse_switch()?,
+            TokenKind::Local => self.parse_local_stmt()?,
+            TokenKind::Function => self.parse_function_stmt()?,
+            TokenKind::Class => self.parse_class_stmt()?,
+            TokenKind::Enum => self.parse_enum()?,
+            TokenKind::Const => self.parse_const()?,
+            TokenKind::Try => self.parse_try()?,
+            TokenKind::Return | TokenKind::Yield => {
+                let keyword = self.bump();
+                let value = if self.at_end_of_stmt() {
+                    None
+                } else {
+                    Some(self.parse_comma_expr()?)
+                };
+                StmtKind::Return { keyword, value }
+            }
+            TokenKind::Break => StmtKind::Break(self.bump()),
+            TokenKind::Continue => StmtKind::Continue(self.bump()),
+            TokenKind::Throw => StmtKind::Throw {
+                throw_token: self.bump(),
+                value: self.parse_comma_expr()?,
+            },
+            _ => StmtKind::Expr(self.parse_comma_expr()?),
+        };
+
+        Ok(Stmt {
+            kind,
+            semicolon: self.eat(&TokenKind::Semicolon),
+        })
+    }
+
+    fn parse_block(&mut self) -> Result<Block<'src>, ParseError> {
+        let brace_open = self.expect(TokenKind::BraceOpen)?;
+        let stmts = self.parse_stmts(|kind| *kind == TokenKind::BraceClose)?;
+        let brace_close = self.expect(TokenKind::BraceClose)?;
+        Ok(Block {
+            brace_open,
+            stmts,
+            brace_close,
+        })
+    }
+
+    // Parses "( <comma expression> )", as used by `if`, `while` and `switch`.
+    fn parse_paren_cond(
+        &mut self,
+    ) -> Result<(TokenReference<'src>, Expr<'src>, TokenReference<'src>), ParseError> {
+        let paren_open = self.expect(TokenKind::ParenOpen)?;
+        let cond = self.parse_comma_expr()?;
+        let paren_close = self.expect(TokenKind::ParenClose)?;
+        Ok((paren_open, cond, paren_close))
+    }
+
+    fn parse_if(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let if_token = self.bump();
+        let (paren_open, cond, paren_close) = self.parse_paren_cond()?;
+        let body = Box::new(self.parse_stmt()?);
+        self.expect_end_of_stmt(true)?;
+
+        let else_branch = match self.eat(&TokenKind::Else) {
+            Some(else_token) => Some((else_token, Box::new(self.parse_stmt()?))),
+            None => None,
+        };
+
+        Ok(StmtKind::If {
+            if_token,
+            paren_open,
+            cond,
+            paren_close,
+            body,
+            else_branch,
+        })
+    }
+
+    fn parse_while(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let while_token = self.bump();
+        let (paren_open, cond, paren_close) = self.parse_paren_cond()?;
+        let body = Box::new(self.parse_stmt()?);
+        Ok(StmtKind::While {
+            while_token,
+            paren_open,
+            cond,
+            paren_close,
+            body,
+        })
+    }
+
+    fn parse_do_while(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let do_token = self.bump();
+        let body = Box::new(self.parse_stmt()?);
+        let while_token = self.expect(TokenKind::While)?;
+        let (paren_open, cond, paren_close) = self.parse_paren_cond()?;
+        Ok(StmtKind::DoWhile {
+            do_token,
+            body,
+            while_token,
+            paren_open,
+            cond,
+            paren_close,
+        })
+    }
+
+    fn parse_for(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let for_token = self.bump();
+        let paren_open = self.expect(TokenKind::ParenOpen)?;
+
+        let init = match self.peek() {
+            TokenKind::Semicolon => None,
+            TokenKind::Local => Some(ForInit::Local(self.parse_local()?)),
+            _ => Some(ForInit::Expr(self.parse_comma_expr()?)),
+        };
+        let first_semicolon = self.expect(TokenKind::Semicolon)?;
+
+        let cond = if self.at(&TokenKind::Semicolon) {
+            None
+        } else {
+            Some(self.parse_comma_expr()?)
+        };
+        let second_semicolon = self.expect(TokenKind::Semicolon)?;
+
+        let step = if self.at(&TokenKind::ParenClose) {
+            None
+        } else {
+            Some(self.parse_comma_expr()?)
+        };
+        let paren_close = self.expect(TokenKind::ParenClose)?;
+
+        let body = Box::new(self.parse_stmt()?);
+        Ok(StmtKind::For {
+            for_token,
+            paren_open,
+            init,
+            first_semicolon,
+            cond,
+            second_semicolon,
+            step,
+            paren_close,
+            body,
+        })
+    }
+
+    fn parse_foreach(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let foreach_token = self.bump();
+        let paren_open = self.expect(TokenKind::ParenOpen)?;
+
+        let first = self.expect_ident()?;
+        let (key, value) = match self.eat(&TokenKind::Comma) {
+            Some(comma) => (Some((first, comma)), self.expect_ident()?),
+            None => (None, first),
+        };
+
+        let in_token = self.expect(TokenKind::In)?;
+        let iterable = self.parse_expr()?;
+        let paren_close = self.expect(TokenKind::ParenClose)?;
+        let body = Box::new(self.parse_stmt()?);
+        Ok(StmtKind::Foreach {
+            foreach_token,
+            paren_open,
+            key,
+            value,
+            in_token,
+            iterable,
+            paren_close,
+            body,
+        })
+    }
+
+    fn parse_switch(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let switch_token = self.bump();
+        let (paren_open, subject, paren_close) = self.parse_paren_cond()?;
+        let brace_open = self.expect(TokenKind::BraceOpen)?;
+
+        let mut cases = Vec::new();
+        while !self.at(&TokenKind::BraceClose) {
+            let label = match self.peek() {
+                TokenKind::Case => CaseLabel::Case(self.bump(), self.parse_expr()?),
+                TokenKind::Default => CaseLabel::Default(self.bump()),
+                _ => {
+                    return Err(
+                        self.error(ParseErrorKind::ExpectedToken(Box::new(TokenKind::Case)))
+                    );
+                }
+            };
+            let colon = self.expect(TokenKind::Colon)?;
+            let stmts = self.parse_stmts(|kind| {
+                matches!(
+                    kind,
+                    TokenKind::Case | TokenKind::Default | TokenKind::BraceClose
+                )
+            })?;
+            cases.push(SwitchCase {
+                label,
+                colon,
+                stmts,
+            });
+        }
+
+        let brace_close = self.bump();
+        Ok(StmtKind::Switch {
+            switch_token,
+            paren_open,
+            subject,
+            paren_close,
+            brace_open,
+            cases,
+            brace_close,
+        })
+    }
+
+    fn parse_local_stmt(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        if self.peek_nth(1).kind != TokenKind::Function {
+            return Ok(StmtKind::Local(self.parse_local()?));
+        }
+
+        let local_token = self.bump();
+        let function_token = self.bump();
+        let name = self.expect_ident()?;
+        let body = self.parse_function_body()?;
+        Ok(StmtKind::LocalFunction {
+            local_token,
+            function_token,
+            name,
+            body,
+        })
+    }
+
+    fn parse_local(&mut self) -> Result<Local<'src>, ParseError> {
+        let local_token = self.expect(TokenKind::Local)?;
+
+        let mut vars = Vec::new();
+        loop {
+            let name = self.expect_ident()?;
+            let init = match self.eat(&TokenKind::Eq) {
+                Some(eq) => Some((eq, self.parse_expr()?)),
+                None => None,
+            };
+            let comma = self.eat(&TokenKind::Comma);
+            let more = comma.is_some();
+            vars.push((LocalVar { name, init }, comma));
+            if !more {
+                break;
+            }
+        }
+
+        Ok(Local { local_token, vars })
+    }
+
+    fn parse_function_stmt(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let function_token = self.bump();
+
+        let first = self.expect_ident()?;
+        let mut rest = Vec::new();
+        while let Some(scope) = self.eat(&TokenKind::Scope) {
+            rest.push((scope, self.expect_ident()?));
+        }
+
+        let body = self.parse_function_body()?;
+        Ok(StmtKind::Function {
+            function_token,
+            name: FunctionName { first, rest },
+            body,
+        })
+    }
+
+    fn parse_function_body(&mut self) -> Result<FunctionBody<'src>, ParseError> {
+        let params = self.parse_params()?;
+        let body = if self.at(&TokenKind::BraceOpen) {
+            Stmt {
+                kind: StmtKind::Block(self.parse_block()?),
+                semicolon: None,
+            }
+        } else {
+            self.parse_stmt()?
+        };
+        Ok(FunctionBody {
+            params,
+            body: Box::new(body),
+        })
+    }
+
+    fn parse_params(&mut self) -> Result<Params<'src>, ParseError> {
+        let paren_open = self.expect(TokenKind::ParenOpen)?;
+
+        let mut params = Vec::new();
+        while !self.at(&TokenKind::ParenClose) {
+            let param = match self.eat(&TokenKind::Ellipsis) {
+                Some(ellipsis) => Param::Varargs(ellipsis),
+                None => {
+                    let name = self.expect_ident()?;
+                    if self.dialect == Dialect::Squirrel2 && self.at(&TokenKind::Eq) {
+                        let syntax = "default parameter values";
+                        return Err(self.error(ParseErrorKind::Squirrel3Syntax(syntax)));
+                    }
+                    let default = match self.eat(&TokenKind::Eq) {
+                        Some(eq) => Some((eq, self.parse_expr()?)),
+                        None => None,
+                    };
+                    Param::Named { name, default }
+                }
+            };
+
+            let comma = self.eat(&TokenKind::Comma);
+            let more = comma.is_some();
+            params.push((param, comma));
+            if !more {
+                break;
+            }
+        }
+
+        let paren_close = self.expect(TokenKind::ParenClose)?;
+        Ok(Params {
+            paren_open,
+            params,
+            paren_close,
+        })
+    }
+
+    fn parse_class_stmt(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let class_token = self.bump();
+        let name = self.parse_postfix()?;
+        let body = Box::new(self.parse_class_body()?);
+        Ok(StmtKind::Class {
+            class_token,
+            name,
+            body,
+        })
+    }
+
+    fn parse_class_body(&mut self) -> Result<ClassBody<'src>, ParseError> {
+        let extends = match self.eat(&TokenKind::Extends) {
+            Some(extends) => Some((extends, Box::new(self.parse_expr()?))),
+            None => None,
+        };
+        let attributes = self.parse_attributes()?;
+        let brace_open = self.expect(TokenKind::BraceOpen)?;
+
+        let mut members = Vec::new();
+        while !self.at(&TokenKind::BraceClose) {
+            let attributes = self.parse_attributes()?;
+            // `static` is an identifier in Squirrel 2, so only a member named `static` may follow it
+            if self.dialect == Dialect::Squirrel2
+                && let TokenKind::Ident(name) = self.peek()
+                && name == "static"
+                && self.peek_nth(1).kind != TokenKind::Eq
+            {
+                return Err(self.error(ParseErrorKind::Squirrel3Syntax("static members")));
+            }
+            let static_token = self.eat(&TokenKind::Static);
+            let entry = self.parse_table_entry()?;
+            let separator = self.eat(&TokenKind::Semicolon);
+            members.push(ClassMember {
+                attributes,
+                static_token,
+                entry,
+                separator,
+            });
+        }
+
+        let brace_close = self.bump();
+        Ok(ClassBody {
+            extends,
+            attributes,
+            brace_open,
+            members,
+            brace_close,
+        })
+    }
+
+    fn parse_attributes(&mut self) -> Result<Option<Attributes<'src>>, ParseError> {
+        let Some(open) = self.eat(&TokenKind::AttrOpen) else {
+            return Ok(None);
+        };
+
+        let mut entries = Vec::new();
+        while !self.at(&TokenKind::AttrClose) {
+            let entry = self.parse_table_entry()?;
+            entries.push((entry, self.eat(&TokenKind::Comma)));
+        }
+        let close = self.bump();
+
+        Ok(Some(Attributes {
+            open,
+            entries,
+            close,
+        }))
+    }
+
+    fn parse_table_entry(&mut self) -> Result<TableEntry<'src>, ParseError> {
+        match self.peek() {
+            TokenKind::Ident(_) => Ok(TableEntry::Field {
+                name: self.bump(),
+                eq: self.expect(TokenKind::Eq)?,
+                value: self.parse_expr()?,
+            }),
+
+            TokenKind::String { .. } | TokenKind::VerbatimString { .. }
+                if self.peek_nth(1).kind == TokenKind::Colon
+                    && self.dialect == Dialect::Squirrel2 =>
+            {
+                Err(self.error(ParseErrorKind::Squirrel3Syntax("JSON-style table entries")))
+            }
+
+            TokenKind::String { .. } | TokenKind::VerbatimString { .. }
+                if self.peek_nth(1).kind == TokenKind::Colon =>
+            {
+                Ok(TableEntry::Json {
+                    key: self.bump(),
+                    colon: self.bump(),
+                    value: self.parse_expr()?,
+                })
+            }
+
+            TokenKind::SquareOpen => Ok(TableEntry::Computed {
+                square_open: self.bump(),
+                key: self.parse_expr()?,
+                square_close: self.expect(TokenKind::SquareClose)?,
+                eq: self.expect(TokenKind::Eq)?,
+                value: self.parse_expr()?,
+            }),
+
+            TokenKind::Function => Ok(TableEntry::Function {
+                function_token: self.bump(),
+                name: self.expect_ident()?,
+                body: self.parse_function_body()?,
+            }),
+
+            TokenKind::Constructor => Ok(TableEntry::Constructor {
+                constructor_token: self.bump(),
+                body: self.parse_function_body()?,
+            }),
+
+            _ => Err(self.error(ParseErrorKind::ExpectedTableEntry)),
+        }
+    }
+
+    fn parse_enum(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let enum_token = self.bump();
+        let name = self.expect_ident()?;
+        let brace_open = self.expect(TokenKind::BraceOpen)?;
+
+        let mut members = Vec::new();
+        while !self.at(&TokenKind::BraceClose) {
+            let name = self.expect_ident()?;
+            let value = match self.eat(&TokenKind::Eq) {
+                Some(eq) => Some((eq, self.parse_scalar()?)),
+                None => None,
+            };
+            members.push((EnumMember { name, value }, self.eat(&TokenKind::Comma)));
+        }
+
+        let brace_close = self.bump();
+        Ok(StmtKind::Enum {
+            enum_token,
+            name,
+            brace_open,
+            members,
+            brace_close,
+        })
+    }
+
+    fn parse_const(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        Ok(StmtKind::Const {
+            const_token: self.bump(),
+            name: self.expect_ident()?,
+            eq: self.expect(TokenKind::Eq)?,
+            value: self.parse_scalar()?,
+        })
+    }
+
+    // Constants and enum values must be literals, `true`, `false`, or a negated number.
+    fn parse_scalar(&mut self) -> Result<Expr<'src>, ParseError> {
+        match self.peek() {
+            kind if kind.is_literal() => Ok(Expr::Atom(self.bump())),
+            TokenKind::True | TokenKind::False => Ok(Expr::Atom(self.bump())),
+            TokenKind::Minus => {
+                let op = self.bump();
+                match self.peek() {
+                    // Character code literals are numbers too
+                    TokenKind::Integer { .. }
+                    | TokenKind::Float { .. }
+                    | TokenKind::Char { .. } => Ok(Expr::Unary {
+                        op,
+                        expr: Box::new(Expr::Atom(self.bump())),
+                    }),
+                    _ => Err(self.error(ParseErrorKind::ExpectedScalar)),
+                }
+            }
+            _ => Err(self.error(ParseErrorKind::ExpectedScalar)),
+        }
+    }
+
+    fn parse_try(&mut self) -> Result<StmtKind<'src>, ParseError> {
+        let try_token = self.bump();
+        let body = Box::new(self.parse_stmt()?);
+        let catch_token = self.expect(TokenKind::Catch)?;
+        let paren_open = self.expect(TokenKind::ParenOpen)?;
+        let binding = self.expect_ident()?;
+        let paren_close = self.expect(TokenKind::ParenClose)?;
+        let catch_body = Box::new(self.parse_stmt()?);
+        Ok(StmtKind::Try {
+            try_token,
+            body,
+            catch_token,
+            paren_open,
+            binding,
+            paren_close,
+            catch_body,
+        })
+    }
+
+    fn parse_comma_expr(&mut self) -> Result<Expr<'src>, ParseError> {
+        let mut expr = self.parse_expr()?;
+        while let Some(op) = self.eat(&TokenKind::Comma) {
+            expr = Expr::Binary {
+                lhs: Box::new(expr),
+                op,
+                rhs: Box::new(self.parse_expr()?),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr<'src>, ParseError> {
+        let expr = self.parse_binary(0)?;
+        match self.peek() {
+            // Assignments are right associative
+            TokenKind::Eq
+            | TokenKind::Ins
+            | TokenKind::PlusEq
+            | TokenKind::MinusEq
+            | TokenKind::MultEq
+            | TokenKind::DivEq
+            | TokenKind::ModEq => Ok(Expr::Binary {
+                lhs: Box::new(expr),
+                op: self.bump(),
+                rhs: Box::new(self.parse_expr()?),
+            }),
+
+            TokenKind::Question => Ok(Expr::Ternary {
+                cond: Box::new(expr),
+                question: self.bump(),
+                then_branch: Box::new(self.parse_expr()?),
+                colon: self.expect(TokenKind::Colon)?,
+                else_branch: Box::new(self.parse_expr()?),
+            }),
+
+            _ => Ok(expr),
+        }
+    }
+
+    // Precedence climbing. All binary operators are left associative.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr<'src>, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(precedence) = binary_precedence(self.peek())
+            && precedence > min_precedence
+        {
+            let op = self.bump();
+            let rhs = self.parse_binary(precedence)?;
+            lhs = Expr::Binary {
+                lhs: Box::new(lhs),
+                op,
+                rhs: Box::new(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr<'src>, ParseError> {
+        match self.peek() {
+            TokenKind::Minus
+            | TokenKind::Not
+            | TokenKind::BitNot
+            | TokenKind::Inc
+            | TokenKind::Dec
+            | TokenKind::Typeof
+            | TokenKind::Clone
+            | TokenKind::Resume
+            | TokenKind::Delete => Ok(Expr::Unary {
+                op: self.bump(),
+                expr: Box::new(self.parse_unary()?),
+            }),
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr<'src>, ParseError> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            expr = match self.peek() {
+                TokenKind::Dot => {
+                    let dot = self.bump();
+                    let name = match self.peek() {
+                        TokenKind::Ident(_) | TokenKind::Constructor => self.bump(),
+                        _ => return Err(self.error(ParseErrorKind::ExpectedIdent)),
+                    };
+                    Expr::Field {
+                        expr: Box::new(expr),
+                        dot,
+                        name,
+                    }
+                }
+
+                TokenKind::SquareOpen if self.newline_before() => {
+                    return Err(self.error(ParseErrorKind::IndexOnNewLine));
+                }
+
+                TokenKind::SquareOpen => Expr::Index {
+                    expr: Box::new(expr),
+                    square_open: self.bump(),
+                    index: Box::new(self.parse_comma_expr()?),
+                    square_close: self.expect(TokenKind::SquareClose)?,
+                },
+
+                TokenKind::ParenOpen => {
+                    let paren_open = self.bump();
+                    let args = self.parse_list(TokenKind::ParenClose)?;
+                    Expr::Call {
+                        expr: Box::new(expr),
+                        paren_open,
+                        args,
+                        paren_close: self.bump(),
+                    }
+                }
+
+                TokenKind::Inc | TokenKind::Dec if !self.newline_before() => Expr::Postfix {
+                    expr: Box::new(expr),
+                    op: self.bump(),
+                },
+
+                _ => return Ok(expr),
+            };
+        }
+    }
+
+    // Parses expressions until `close`, which is left for the caller to bump. Like the reference
+    // compiler, commas between the expressions are optional.
+    fn parse_list(
+        &mut self,
+        close: TokenKind<'static>,
+    ) -> Result<Punctuated<'src, Expr<'src>>, ParseError> {
+        let mut items = Vec::new();
+        while !self.at(&close) {
+            if self.at(&TokenKind::Eof) {
+                return Err(self.error(ParseErrorKind::ExpectedToken(Box::new(close))));
+            }
+
+            let item = self.parse_expr()?;
+            items.push((item, self.eat(&TokenKind::Comma)));
+        }
+        Ok(items)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr<'src>, ParseError> {
+        match self.peek() {
+            kind if kind.is_literal() => Ok(Expr::Atom(self.bump())),
+            TokenKind::Ident(_)
+            | TokenKind::Null
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::This
+            | TokenKind::Base
+            | TokenKind::Constructor
+            | TokenKind::Rawcall
+            | TokenKind::File
+            | TokenKind::Line
+            | TokenKind::Parent
+            | TokenKind::Vargc
+            | TokenKind::Vargv => Ok(Expr::Atom(self.bump())),
+
+            TokenKind::Scope => Ok(Expr::Root {
+                scope: self.bump(),
+                name: self.expect_ident()?,
+            }),
+
+            TokenKind::ParenOpen => Ok(Expr::Paren {
+                paren_open: self.bump(),
+                expr: Box::new(self.parse_comma_expr()?),
+                paren_close: self.expect(TokenKind::ParenClose)?,
+            }),
+
+            TokenKind::SquareOpen => {
+                let square_open = self.bump();
+                let items = self.parse_list(TokenKind::SquareClose)?;
+                Ok(Expr::Array {
+                    square_open,
+                    items,
+                    square_close: self.bump(),
+                })
+            }
+
+            TokenKind::BraceOpen => {
+                let brace_open = self.bump();
+                let mut entries = Vec::new();
+                while !self.at(&TokenKind::BraceClose) {
+                    let entry = self.parse_table_entry()?;
+                    entries.push((entry, self.eat(&TokenKind::Comma)));
+                }
+                Ok(Expr::Table {
+                    brace_open,
+                    entries,
+                    brace_close: self.bump(),
+                })
+            }
+
+            TokenKind::Function => Ok(Expr::Function {
+                function_token: self.bump(),
+                body: self.parse_function_body()?,
+            }),
+
+            TokenKind::At if self.dialect == Dialect::Squirrel2 => {
+                Err(self.error(ParseErrorKind::Squirrel3Syntax("lambdas")))
+            }
+
+            TokenKind::At => Ok(Expr::Lambda {
+                at: self.bump(),
+                params: self.parse_params()?,
+                body: Box::new(self.parse_expr()?),
+            }),
+
+            TokenKind::Class => Ok(Expr::Class {
+                class_token: self.bump(),
+                body: Box::new(self.parse_class_body()?),
+            }),
+
+            TokenKind::Delegate => Ok(Expr::Delegate {
+                delegate_token: self.bump(),
+                parent: Box::new(self.parse_expr()?),
+                colon: self.expect(TokenKind::Colon)?,
+                expr: Box::new(self.parse_expr()?),
+            }),
+
+            _ => Err(self.error(ParseErrorKind::ExpectedExpression)),
+        }
+    }
+}
+
+fn position_after(token: &Token<'_>) -> (u32, u32) {
+    if let TokenKind::Newline(_) = token.kind {
+        (token.end_line + 1, 1)
+    } else {
+        (token.end_line, token.end_column + 1)
+    }
+}
+
+/// The precedence of a binary operator, from 1 for `||` to 10 for `*`, `/` and `%`. Assignments and
+/// the comma operator are handled separately and have none.
+pub(crate) fn binary_precedence(kind: &TokenKind<'_>) -> Option<u8> {
+    let precedence = match kind {
+        TokenKind::Or => 1,
+        TokenKind::And => 2,
+        TokenKind::BitOr => 3,
+        TokenKind::BitXor => 4,
+        TokenKind::BitAnd => 5,
+        TokenKind::EqEq | TokenKind::Neq | TokenKind::Spaceship => 6,
+        TokenKind::Lt
+        | TokenKind::Le
+        | TokenKind::Gt
+        | TokenKind::Ge
+        | TokenKind::In
+        | TokenKind::Instanceof => 7,
+        TokenKind::BitLeft | TokenKind::BitRight | TokenKind::BitUnsRight => 8,
+        TokenKind::Plus | TokenKind::Minus => 9,
+        TokenKind::Mult | TokenKind::Div | TokenKind::Mod => 10,
+        _ => return None,
+    };
+    Some(precedence)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseErrorKind {
+    /// A specific token was expected, but something else was encountered.
+    ExpectedToken(Box<TokenKind<'static>>),
+    /// An identifier was expected, but something else was encountered.
+    ExpectedIdent,
+    /// An expression was expected, but something else was encountered.
+    ExpectedExpression,
+    /// A scalar, i.e. a literal, `true`, `false` or a negated number, was expected as the value of a
+    /// constant or an enum member.
+    ExpectedScalar,
+    /// A slot of a table, class or attribute list was expected, but something else was encountered.
+    ExpectedTableEntry,
+    /// A statement did not end with a `;`, a newline or a `}`.
+    ExpectedEndOfStatement,
+    /// A `[` started a line right after an expression, which the reference compiler rejects rather
+    /// than guess whether it indexes the expression or starts something new.
+    IndexOnNewLine,
+    /// Syntax which was added in Squirrel 3, e.g. lambdas, in a Squirrel 2 script.
+    Squirrel3Syntax(&'static str),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::ExpectedToken(kind) => write!(f, "expected `{kind}`"),
+            ParseErrorKind::ExpectedIdent => f.write_str("expected an identifier"),
+            ParseErrorKind::ExpectedExpression => f.write_str("expected an expression"),
+            ParseErrorKind::ExpectedScalar => f.write_str("expected a literal value"),
+            ParseErrorKind::ExpectedTableEntry => f.write_str("expected a slot"),
+            ParseErrorKind::ExpectedEndOfStatement => {
+                f.write_str("expected `;`, a new line or `}` after the statement")
+            }
+            ParseErrorKind::IndexOnNewLine => f.write_str(
+                "a `[` can't start a line after an expression, add a `,` or `;` before it",
+            ),
+            ParseErrorKind::Squirrel3Syntax(syntax) => {
+                write!(f, "{syntax} need Squirrel 3, but the dialect is 2.2")
+            }
+        }
+    }
+}
+
+#[derive(Constructor, Debug, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}:{}", self.kind, self.line, self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, LexerError};
+    use ParseErrorKind::*;
+
+    fn parse(source: &str) -> Result<Chunk<'_>, ParseError> {
+        let tokens = Lexer::new(source)
+            .collect::<Result<Vec<Token>, LexerError>>()
+            .unwrap();
+        Parser::new(tokens).parse()
+    }
+
+    fn parse_stmts(source: &str) -> Vec<Stmt<'_>> {
+        parse(source).unwrap().stmts
+    }
+
+    fn parse_expr(source: &str) -> Expr<'_> {
+        match parse_stmts(source).pop().unwrap().kind {
+            StmtKind::Expr(expr) => expr,
+            kind => panic!("expected an expression statement, got {kind:?}"),
+        }
+    }
+
+    fn text(token: &Token<'_>) -> String {
+        match &token.kind {
+            TokenKind::Ident(value) => value.to_string(),
+            kind if kind.is_literal() => kind.to_string(),
+            kind => format!("{kind:?}"),
+        }
+    }
+
+    // Renders an expression fully parenthesised, which makes precedence easy to assert on.
+    fn sexp(expr: &Expr<'_>) -> String {
+        match expr {
+            Expr::Atom(token) => text(token),
+            Expr::Root { name, .. } => format!("::{}", text(name)),
+            Expr::Paren { expr, .. } => sexp(expr),
+            Expr::Unary { op, expr } => format!("({:?} {})", op.kind, sexp(expr)),
+            Expr::Postfix { expr, op } => format!("({} {:?})", sexp(expr), op.kind),
+            Expr::Binary { lhs, op, rhs } => {
+                format!("({} {:?} {})", sexp(lhs), op.kind, sexp(rhs))
+            }
+            Expr::Ternary {
+                cond,
+                then_branch,
+                else_branch,
+                ..
+            } => format!(
+                "({} ? {} : {})",
+                sexp(cond),
+                sexp(then_branch),
+                sexp(else_branch)
+            ),
+            Expr::Field { expr, name, .. } => format!("{}.{}", sexp(expr), text(name)),
+            Expr::Index { expr, index, .. } => format!("{}[{}]", sexp(expr), sexp(index)),
+            Expr::Call { expr, args, .. } => {
+                let args = args.iter().map(|(arg, _)| sexp(arg)).collect::<Vec<_>>();
+                format!("{}({})", sexp(expr), args.join(" "))
+            }
+            Expr::Array { items, .. } => {
+                let items = items.iter().map(|(item, _)| sexp(item)).collect::<Vec<_>>();
+                format!("[{}]", items.join(" "))
+            }
+            Expr::Table { entries, .. } => format!("{{{}}}", entries.len()),
+            Expr::Function { .. } => "function".into(),
+            Expr::Lambda { body, .. } => format!("@{}", sexp(body)),
+            Expr::Class { .. } => "class".into(),
+            Expr::Delegate { parent, expr, .. } => {
+                format!("(delegate {} {})", sexp(parent), sexp(expr))
+            }
+        }
+    }
+
+    macro_rules! assert_expr {
+        ($source: expr, $sexp: expr) => {{
+            assert_eq!(sexp(&parse_expr($source)), $sexp);
+        }};
+    }
+
+    macro_rules! assert_error {
+        (
+            $source: expr,
+            $kind: expr,
+            $line: expr,
+            $column: expr
+        ) => {{
+            let error = parse($source).unwrap_err();
+            assert_eq!(error, ParseError::new($kind, $line, $column));
+        }};
+    }
+
+    #[test]
+    fn empty() {
+        let chunk = parse("").unwrap();
+        assert!(chunk.stmts.is_empty());
+        assert_eq!(
+            chunk.eof.token,
+            Token::new(TokenKind::Eof, 1, 1, 1, 1, 0, 0)
+        );
+
+        let chunk = parse("// nothing\n").unwrap();
+        assert!(chunk.stmts.is_empty());
+        assert_eq!(
+            chunk.eof.token,
+            Token::new(TokenKind::Eof, 2, 1, 2, 1, 11, 11)
+        );
+    }
+
+    #[test]
+    fn trivia() {
+        let stmts = parse_stmts("// leading\n\na = /* inline */ 1 // trailing\n\n b");
+        let StmtKind::Expr(Expr::Binary { lhs, op, rhs }) = &stmts[0].kind else {
+            panic!("expected a binary expression");
+        };
+        let Expr::Atom(a) = &**lhs else {
+            panic!("expected an atom");
+        };
+        let Expr::Atom(one) = &**rhs else {
+            panic!("expected an atom");
+        };
+
+        let kinds = |trivia: &[Token]| {
+            trivia
+                .iter()
+                .map(|t| t.kind.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds(&a.leading_trivia), ["// leading", "\n", "\n"]);
+        assert_eq!(kinds(&a.trailing_trivia), [" "]);
+        assert_eq!(kinds(&op.trailing_trivia), [" ", "/* inline */", " "]);
+        assert_eq!(kinds(&one.trailing_trivia), [" ", "// trailing", "\n"]);
+
+        let StmtKind::Expr(Expr::Atom(b)) = &stmts[1].kind else {
+            panic!("expected an atom");
+        };
+        assert_eq!(kinds(&b.leading_trivia), ["\n", " "]);
+    }
+
+    #[test]
+    fn precedence() {
+        assert_expr!("a + b * c", "(a Plus (b Mult c))");
+        assert_expr!("a - b - c", "((a Minus b) Minus c)");
+        assert_expr!(
+            "a || b && c | d ^ e & f",
+            "(a Or (b And (c BitOr (d BitXor (e BitAnd f)))))"
+        );
+        assert_expr!("a == b < c << d", "(a EqEq (b Lt (c BitLeft d)))");
+        assert_expr!("a in b instanceof c", "((a In b) Instanceof c)");
+        assert_expr!("(a + b) * c", "((a Plus b) Mult c)");
+        assert_expr!("a <=> b", "(a Spaceship b)");
+    }
+
+    #[test]
+    fn assignment_and_ternary() {
+        assert_expr!("a = b = c", "(a Eq (b Eq c))");
+        assert_expr!("a <- b + 1", "(a Ins (b Plus 1))");
+        assert_expr!("a += b ? c : d", "(a PlusEq (b ? c : d))");
+        assert_expr!("a ? b : c ? d : e", "(a ? b : (c ? d : e))");
+        assert_expr!("a = 1, b = 2", "((a Eq 1) Comma (b Eq 2))");
+    }
+
+    #[test]
+    fn unary_and_postfix() {
+        assert_expr!("-a * b", "((Minus a) Mult b)");
+        assert_expr!("!a.b", "(Not a.b)");
+        assert_expr!("- -a", "(Minus (Minus a))");
+        assert_expr!("typeof a == \"table\"", "((Typeof a) EqEq \"table\")");
+        assert_expr!("delete a.b", "(Delete a.b)");
+        assert_expr!("clone a", "(Clone a)");
+        assert_expr!("resume a", "(Resume a)");
+        assert_expr!("++a", "(Inc a)");
+        assert_expr!("a--", "(a Dec)");
+        assert_expr!("-a++", "(Minus (a Inc))");
+    }
+
+    #[test]
+    fn postfix_chains() {
+        assert_expr!("a.b[c](d, e).f", "a.b[c](d e).f");
+        assert_expr!("::print(\"hi\")", "::print(\"hi\")");
+        assert_expr!("this.constructor", "This.Constructor");
+        assert_expr!("rawcall(f, this)", "Rawcall(f This)");
+        assert_expr!("f(a b)", "f(a b)");
+    }
+
+    #[test]
+    fn squirrel2() {
+        let parse_expr = |source| {
+            let tokens = Lexer::new(source)
+                .with_dialect(Dialect::Squirrel2)
+                .collect::<Result<Vec<Token>, LexerError>>()
+                .unwrap();
+            match Parser::new(tokens)
+                .with_dialect(Dialect::Squirrel2)
+                .parse()
+                .unwrap()
+                .stmts
+                .pop()
+                .unwrap()
+                .kind
+            {
+                StmtKind::Expr(expr) => sexp(&expr),
+                kind => panic!("expected an expression statement, got {kind:?}"),
+            }
+        };
+        assert_eq!(
+            parse_expr("t = delegate parent : {a = 1}"),
+            "(t Eq (delegate Parent {1}))"
+        );
+        assert_eq!(parse_expr("vargv[vargc - 1]"), "Vargv[(Vargc Minus 1)]");
+        // Squirrel 3 keywords are identifiers
+        assert_eq!(parse_expr("static(__LINE__)"), "static(__LINE__)");
+    }
+
+    fn parse_in(source: &str, dialect: Dialect) -> Result<Chunk<'_>, ParseError> {
+        let tokens = Lexer::new(source)
+            .with_dialect(dialect)
+            .collect::<Result<Vec<Token>, LexerError>>()
+            .unwrap();
+        Parser::new(tokens).with_dialect(dialect).parse()
+    }
+
+    #[test]
+    fn lambdas_by_dialect() {
+        let source = "f <- @(x) x + 1";
+        assert!(parse_in(source, Dialect::Squirrel3).is_ok());
+        assert_eq!(
+            parse_in(source, Dialect::Squirrel2).unwrap_err(),
+            ParseError::new(Squirrel3Syntax("lambdas"), 1, 6)
+        );
+    }
+
+    #[test]
+    fn default_parameters_by_dialect() {
+        let source = "function f(a, b = 1) {}";
+        assert!(parse_in(source, Dialect::Squirrel3).is_ok());
+        assert_eq!(
+            parse_in(source, Dialect::Squirrel2).unwrap_err(),
+            ParseError::new(Squirrel3Syntax("default parameter values"), 1, 17)
+        );
+    }
+
+    #[test]
+    fn json_tables_by_dialect() {
+        let source = "t <- {a = 1, \"b\": 2}";
+        assert!(parse_in(source, Dialect::Squirrel3).is_ok());
+        assert_eq!(
+            parse_in(source, Dialect::Squirrel2).unwrap_err(),
+            ParseError::new(Squirrel3Syntax("JSON-style table entries"), 1, 14)
+        );
+    }
+
+    #[test]
+    fn static_members_by_dialect() {
+        let source = "class A {\n    static x = 1\n}";
+        assert!(parse_in(source, Dialect::Squirrel3).is_ok());
+        assert_eq!(
+            parse_in(source, Dialect::Squirrel2).unwrap_err(),
+            ParseError::new(Squirrel3Syntax("static members"), 2, 5)
+        );
+        // A member named `static` is fine in Squirrel 2
+        assert!(parse_in("class A {\n    static = 1\n}", Dialect::Squirrel2).is_ok());
+    }
+
+    #[test]
+    fn literals() {
+        assert_expr!("[1, 2, 3]", "[1 2 3]");
+        assert_expr!("[1 2, [3]]", "[1 2 [3]]");
+        // Without a comma, a "[" on the same line is an index
+        assert_expr!("[1 2 [3]]", "[1 2[3]]");
+        assert_expr!("[]", "[]");
+        assert_expr!("x = {}", "(x Eq {0})");
+        assert_expr!(
+            "x = {a = 1, [b] = 2, \"c\": 3, function d() {}}",
+            "(x Eq {4})"
+        );
+        assert_expr!("x = {a = 1 b = 2}", "(x Eq {2})");
+        assert_expr!("f(function(a, b = 1, ...) { return a })", "f(function)");
+        assert_expr!("f(@(a) a + 1)", "f(@(a Plus 1))");
+        assert_expr!("x = class extends Foo {}", "(x Eq class)");
+        assert_expr!("__FILE__ + __LINE__", "(File Plus Line)");
+    }
+
+    #[test]
+    fn newline_sensitivity() {
+        // Postfix "++" doesn't continue an expression on a previous line, and "[" is an error there
+        assert_eq!(parse_stmts("a\n++b").len(), 2);
+        assert_error!("a\n[1]", IndexOnNewLine, 2, 1);
+        assert_error!("x = [a\n[0]]", IndexOnNewLine, 2, 1);
+        assert_error!("x = {[a] = 1\n[b] = 2}", IndexOnNewLine, 2, 1);
+        assert_eq!(parse_stmts("a;\n[1]").len(), 2);
+        assert_expr!("[a,\n[0]]", "[a [0]]");
+        // but a call does
+        assert_eq!(parse_stmts("a\n(1)").len(), 1);
+        // `return` at the end of a line returns nothing
+        let stmts = parse_stmts("return\na");
+        assert_eq!(stmts.len(), 2);
+        assert!(matches!(
+            stmts[0].kind,
+            StmtKind::Return { value: None, .. }
+        ));
+    }
+
+    #[test]
+    fn semicolons() {
+        let stmts = parse_stmts("a = 1; b = 2\nc = 3;;");
+        assert_eq!(stmts.len(), 4);
+        assert!(stmts[0].semicolon.is_some());
+        assert!(stmts[1].semicolon.is_none());
+        assert!(stmts[2].semicolon.is_some());
+        assert!(matches!(stmts[3].kind, StmtKind::Empty));
+
+        assert_eq!(parse_stmts("{ a = 1 } b = 2").len(), 2);
+        assert_eq!(parse_stmts("if (a) b = 1 else c = 2").len(), 1);
+    }
+
+    #[test]
+    fn control_flow() {
+        let stmts = parse_stmts(
+            r#"
+            if (a) b(); else if (c) { d() } else e()
+            while (i < 10) i++
+            do { i-- } while (i)
+            for (local i = 0, j = 1; i < 10; i++, j++) print(i)
+            for (;;) break
+            foreach (v in t) continue
+            foreach (k, v in t) {}
+            switch (x) {
+                case 1:
+                case 2: a(); break
+                default: b()
+            }
+            try { throw "error" } catch (e) { print(e) }
+            "#,
+        );
+
+        assert!(matches!(
+            stmts[0].kind,
+            StmtKind::If {
+                else_branch: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(stmts[1].kind, StmtKind::While { .. }));
+        assert!(matches!(stmts[2].kind, StmtKind::DoWhile { .. }));
+        assert!(matches!(
+            stmts[3].kind,
+            StmtKind::For {
+                init: Some(ForInit::Local(_)),
+                step: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(
+            stmts[4].kind,
+            StmtKind::For {
+                init: None,
+                cond: None,
+                step: None,
+                ..
+            }
+        ));
+        assert!(matches!(stmts[5].kind, StmtKind::Foreach { key: None, .. }));
+        assert!(matches!(
+            stmts[6].kind,
+            StmtKind::Foreach { key: Some(_), .. }
+        ));
+        let StmtKind::Switch { cases, .. } = &stmts[7].kind else {
+            panic!("expected a switch statement");
+        };
+        assert_eq!(cases.len(), 3);
+        assert!(cases[0].stmts.is_empty());
+        assert_eq!(cases[1].stmts.len(), 2);
+        assert!(matches!(cases[2].label, CaseLabel::Default(_)));
+        assert!(matches!(stmts[8].kind, StmtKind::Try { .. }));
+    }
+
+    #[test]
+    fn declarations() {
+        let stmts = parse_stmts(
+            r#"
+            local a, b = 1, c
+            local function f(x) { return x }
+            function Foo::bar::baz() {}
+            const PI = 3.14
+            const NEG = -1
+            enum Colour { Red, Green = 2 Blue = "blue" }
+            "#,
+        );
+
+        let StmtKind::Local(local) = &stmts[0].kind else {
+            panic!("expected a local statement");
+        };
+        assert_eq!(local.vars.len(), 3);
+        assert!(local.vars[1].0.init.is_some());
+        assert!(matches!(stmts[1].kind, StmtKind::LocalFunction { .. }));
+        let StmtKind::Function { name, .. } = &stmts[2].kind else {
+            panic!("expected a function statement");
+        };
+        assert_eq!(name.rest.len(), 2);
+        assert!(matches!(stmts[3].kind, StmtKind::Const { .. }));
+        assert!(matches!(stmts[4].kind, StmtKind::Const { .. }));
+        let StmtKind::Enum { members, .. } = &stmts[5].kind else {
+            panic!("expected an enum statement");
+        };
+        assert_eq!(members.len(), 3);
+    }
+
+    #[test]
+    fn classes() {
+        let stmts = parse_stmts(
+            r#"
+            class Foo.Bar extends Baz </ author = "me" /> {
+                </ test = 1, other = "x" />
+                x = 1;
+                static y = [];
+                [z] = null
+                constructor(a) { base.constructor(a) }
+                function get() { return this.x }
+            }
+            "#,
+        );
+
+        let StmtKind::Class { name, body, .. } = &stmts[0].kind else {
+            panic!("expected a class statement");
+        };
+        assert_eq!(sexp(name), "Foo.Bar");
+        assert!(body.extends.is_some());
+        assert_eq!(body.attributes.as_ref().unwrap().entries.len(), 1);
+        assert_eq!(body.members.len(), 5);
+        assert_eq!(
+            body.members[0].attributes.as_ref().unwrap().entries.len(),
+            2
+        );
+        assert!(body.members[0].separator.is_some());
+        assert!(body.members[1].static_token.is_some());
+        assert!(matches!(
+            body.members[3].entry,
+            TableEntry::Constructor { .. }
+        ));
+        assert!(matches!(body.members[4].entry, TableEntry::Function { .. }));
+    }
+
+    #[test]
+    fn attributes() {
+        let stmts = parse_stmts("class A </ x = 4 / 2, y = 1 > 0 /> {}");
+        let StmtKind::Class { body, .. } = &stmts[0].kind else {
+            panic!("expected a class statement");
+        };
+        assert_eq!(body.attributes.as_ref().unwrap().entries.len(), 2);
+
+        // "</" and "/>" must be written without anything in between
+        assert_error!("class A { < / x = 1 / > y = 1 }", ExpectedTableEntry, 1, 11);
+        // Like in the reference compiler, "/>" always closes attributes
+        assert_error!(
+            "class A </ x = 4 />2 /> {}",
+            ExpectedToken(Box::new(TokenKind::BraceOpen)),
+            1,
+            20
+        );
+    }
+
+    #[test]
+    fn error_expected_token() {
+        assert_error!("if a", ExpectedToken(Box::new(TokenKind::ParenOpen)), 1, 4);
+        assert_error!("f(a", ExpectedToken(Box::new(TokenKind::ParenClose)), 1, 4);
+        assert_error!("{", ExpectedToken(Box::new(TokenKind::BraceClose)), 1, 2);
+        assert_error!(
+            "do a() b()",
+            ExpectedToken(Box::new(TokenKind::While)),
+            1,
+            8
+        );
+    }
+
+    #[test]
+    fn error_expected_ident() {
+        assert_error!("local 1", ExpectedIdent, 1, 7);
+        assert_error!("a.1", ExpectedIdent, 1, 3);
+        assert_error!("foreach (1 in a) {}", ExpectedIdent, 1, 10);
+    }
+
+    #[test]
+    fn error_expected_expression() {
+        assert_error!("a = ", ExpectedExpression, 1, 5);
+        assert_error!("a = )", ExpectedExpression, 1, 5);
+    }
+
+    #[test]
+    fn error_expected_scalar() {
+        assert_error!("const A = b", ExpectedScalar, 1, 11);
+        assert_error!("enum E { A = -\"x\" }", ExpectedScalar, 1, 15);
+    }
+
+    #[test]
+    fn error_expected_table_entry() {
+        assert_error!("a = { 1 }", ExpectedTableEntry, 1, 7);
+        assert_error!("class A { local x }", ExpectedTableEntry, 1, 11);
+    }
+
+    #[test]
+    fn error_expected_end_of_statement() {
+        assert_error!("a = 1 b = 2", ExpectedEndOfStatement, 1, 7);
+        assert_error!("while (a) b() c()", ExpectedEndOfStatement, 1, 15);
+    }
+}
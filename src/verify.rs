@@ -0,0 +1,300 @@
+//! Checks on the output of the formatter, so that a bug in it can't silently change what a script
+//! does.
+
+use std::fmt;
+
+use similar::TextDiff;
+
+use crate::config::Config;
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{Lexer, Token, TokenKind};
+use crate::line_index::LineIndex;
+use crate::{FormatError, format_str};
+
+/// The first difference between the tokens of a script and the tokens of its formatted output.
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    /// The token of the source, or `None` if the output has more tokens.
+    pub expected: Option<Box<TokenKind<'static>>>,
+    /// The token of the output, or `None` if the output has fewer tokens.
+    pub found: Option<Box<TokenKind<'static>>>,
+    /// The position of the token in the source, or the end of the source.
+    pub line: u32,
+    pub column: u32,
+    /// The position of the token in the output, or the end of the output.
+    pub output_line: u32,
+    pub output_column: u32,
+}
+
+impl Divergence {
+    /// The divergence as an error in the source, ready to be shown to a user.
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            message: format!(
+                "formatting would turn {} into {} at {}:{} of the output",
+                describe(self.expected.as_deref()),
+                describe(self.found.as_deref()),
+                self.output_line,
+                self.output_column
+            ),
+            help: Some("this is a bug in squirrelfmt, the script was left as it is"),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "formatting would turn {} at {}:{} into {} at {}:{}",
+            describe(self.expected.as_deref()),
+            self.line,
+            self.column,
+            describe(self.found.as_deref()),
+            self.output_line,
+            self.output_column
+        )
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+fn describe(kind: Option<&TokenKind<'_>>) -> String {
+    match kind {
+        Some(kind) if is_comment(kind) => "a comment".to_owned(),
+        Some(kind) => format!("`{kind}`"),
+        None => "the end of the script".to_owned(),
+    }
+}
+
+/// Checks that formatting only changed whitespace, newlines and the layout of comments: the output
+/// must have the same tokens as the source, and the same comments apart from their whitespace.
+/// Comments may move between tokens.
+pub fn check_tokens<'src>(source: &'src str, output: &'src str) -> Result<(), Divergence> {
+    let (source_tokens, output_tokens) = (tokens(source), tokens(output));
+    let code = |tokens: &[Token<'src>]| -> Vec<Item<'src>> {
+        let tokens = tokens.iter().filter(|token| !is_comment(&token.kind));
+        tokens
+            .map(|token| Item::new(token, token.kind.clone()))
+            .collect()
+    };
+    let comments = |tokens: &[Token<'src>]| -> Vec<Item<'src>> {
+        let comments = tokens.iter().filter(|token| is_comment(&token.kind));
+        comments
+            .map(|comment| Item::new(comment, normalize(&comment.kind)))
+            .collect()
+    };
+
+    let code = compare(
+        (source, &code(&source_tokens)),
+        (output, &code(&output_tokens)),
+    );
+    let comments = compare(
+        (source, &comments(&source_tokens)),
+        (output, &comments(&output_tokens)),
+    );
+    // The difference coming first in the source is reported
+    match (code, comments) {
+        (Err(a), Err(b)) => Err(if (b.line, b.column) < (a.line, a.column) {
+            b
+        } else {
+            a
+        }),
+        (code, comments) => code.and(comments),
+    }
+}
+
+// The tokens of a script apart from whitespace and newlines. Invalid tokens are kept as
+// `TokenKind::Error`, so that they make a difference too.
+fn tokens(source: &str) -> Vec<Token<'_>> {
+    Lexer::recovering(source)
+        .filter_map(Result::ok)
+        .filter(|token| !matches!(token.kind, TokenKind::Whitespace(_) | TokenKind::Newline(_)))
+        .collect()
+}
+
+// A token to compare, with its position
+struct Item<'src> {
+    kind: TokenKind<'src>,
+    line: u32,
+    column: u32,
+}
+
+impl<'src> Item<'src> {
+    fn new(token: &Token<'_>, kind: TokenKind<'src>) -> Self {
+        Item {
+            kind,
+            line: token.start_line,
+            column: token.start_column,
+        }
+    }
+}
+
+fn compare(
+    (source, expected): (&str, &[Item<'_>]),
+    (output, found): (&str, &[Item<'_>]),
+) -> Result<(), Divergence> {
+    fn kind<'a, 'src>(items: &'a [Item<'src>], index: usize) -> Option<&'a TokenKind<'src>> {
+        items.get(index).map(|item| &item.kind)
+    }
+    let index = (0..expected.len().max(found.len()))
+        .find(|&index| kind(expected, index) != kind(found, index));
+    let Some(index) = index else {
+        return Ok(());
+    };
+
+    let owned = |kind: Option<&TokenKind<'_>>| kind.map(|kind| Box::new(kind.clone().into_owned()));
+    let position = |source: &str, item: Option<&Item<'_>>| match item {
+        Some(item) => (item.line, item.column),
+        None => LineIndex::new(source).line_column(source.len()),
+    };
+    let (line, column) = position(source, expected.get(index));
+    let (output_line, output_column) = position(output, found.get(index));
+    Err(Divergence {
+        // Only the reported tokens outlive the scripts
+        expected: owned(kind(expected, index)),
+        found: owned(kind(found, index)),
+        line,
+        column,
+        output_line,
+        output_column,
+    })
+}
+
+/// Output of the formatter which changes when formatted again, e.g. because it oscillates between
+/// two layouts.
+#[derive(Debug, PartialEq)]
+pub struct Unstable {
+    /// The output of formatting once.
+    pub first: String,
+    /// The output of formatting it again.
+    pub second: String,
+}
+
+impl Unstable {
+    /// A unified diff from the first output to the second, with a line of context around changes.
+    pub fn diff(&self) -> String {
+        let diff = TextDiff::from_lines(&self.first, &self.second);
+        let mut diff = diff.unified_diff();
+        diff.context_radius(1).header("first pass", "second pass");
+        diff.to_string()
+    }
+}
+
+impl fmt::Display for Unstable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "formatting again changed the output:\n{}", self.diff())
+    }
+}
+
+impl std::error::Error for Unstable {}
+
+/// Checks that formatting the output of the formatter doesn't change it anymore. Fails with
+/// [`FormatError::Unstable`] if it does, or with the errors of the output if it doesn't parse.
+pub fn check_idempotent(formatted: &str, config: &Config) -> Result<(), FormatError> {
+    let second = format_str(formatted, config)?;
+    if second != formatted {
+        let first = formatted.to_owned();
+        return Err(FormatError::Unstable(Unstable { first, second }));
+    }
+    Ok(())
+}
+
+fn is_comment(kind: &TokenKind<'_>) -> bool {
+    matches!(
+        kind,
+        TokenKind::Comment(_)
+            | TokenKind::MultiLineComment(_)
+            | TokenKind::DocComment(_)
+            | TokenKind::DocBlockComment(_)
+    )
+}
+
+// A comment with each run of whitespace replaced by a single space, since formatting re-indents
+// multi-line comments and trims trailing whitespace.
+fn normalize<'src>(kind: &TokenKind<'src>) -> TokenKind<'src> {
+    let collapse = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match kind {
+        TokenKind::Comment(text) => TokenKind::Comment(collapse(text).into()),
+        TokenKind::MultiLineComment(text) => TokenKind::MultiLineComment(collapse(text).into()),
+        TokenKind::DocComment(text) => TokenKind::DocComment(collapse(text).into()),
+        TokenKind::DocBlockComment(text) => TokenKind::DocBlockComment(collapse(text).into()),
+        kind => kind.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equivalent() {
+        assert_eq!(check_tokens("a=1", "a = 1\n"), Ok(()));
+        // Comments are re-indented
+        assert_eq!(
+            check_tokens(
+                "f(a, /* x\n     y */ b) // z  ",
+                "f(a, /* x\n y */ b) // z\n"
+            ),
+            Ok(())
+        );
+        // Comments may move
+        assert_eq!(check_tokens("f(a /* x */, b)", "f(a, /* x */ b)\n"), Ok(()));
+    }
+
+    #[test]
+    fn idempotent() {
+        let config = Config::default();
+        assert!(check_idempotent("a = 1\n", &config).is_ok());
+        let Err(FormatError::Unstable(unstable)) = check_idempotent("x = 0\na=1\nb = 2\n", &config)
+        else {
+            panic!("expected unstable output");
+        };
+        assert_eq!(
+            unstable.diff(),
+            "--- first pass\n+++ second pass\n@@ -1,3 +1,3 @@\n x = 0\n-a=1\n+a = 1\n b = 2\n"
+        );
+        assert!(matches!(
+            check_idempotent("a = ", &config),
+            Err(FormatError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn divergences() {
+        assert_eq!(
+            check_tokens("a = 1\nb = a - 1\n", "a = 1\nb = a + 1\n"),
+            Err(Divergence {
+                expected: Some(Box::new(TokenKind::Minus)),
+                found: Some(Box::new(TokenKind::Plus)),
+                line: 2,
+                column: 7,
+                output_line: 2,
+                output_column: 7,
+            })
+        );
+        assert_eq!(
+            check_tokens("a; // one\nb", "a;\nb\n").unwrap_err(),
+            Divergence {
+                expected: Some(Box::new(TokenKind::Comment("// one".into()))),
+                found: None,
+                line: 1,
+                column: 4,
+                output_line: 3,
+                output_column: 1,
+            }
+        );
+        // Output which doesn't even lex
+        let divergence = check_tokens("s = \"a\"", "s = \"a\n").unwrap_err();
+        assert_eq!(
+            divergence.found.as_deref(),
+            Some(&TokenKind::Error("\"a".into()))
+        );
+        assert_eq!(
+            divergence.to_string(),
+            "formatting would turn `\"a\"` at 1:5 into `\"a` at 1:5"
+        );
+    }
+}
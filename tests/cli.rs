@@ -0,0 +1,182 @@
+//! Runs the `squirrelfmt` binary on scripts in temporary directories and on stdin.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const UNFORMATTED: &str = "x=1\n";
+const FORMATTED: &str = "x = 1\n";
+
+// A directory of its own for each test, removed at the end of it
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("squirrelfmt-cli-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    fn write(&self, path: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn squirrelfmt(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_squirrelfmt"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn squirrelfmt_stdin(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_squirrelfmt"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn formats_in_place() {
+    let dir = TempDir::new("in-place");
+    let unformatted = dir.write("a.nut", UNFORMATTED);
+    let formatted = dir.write("b.nut", FORMATTED);
+
+    let output = squirrelfmt(&[&unformatted, &formatted]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(read(&unformatted), FORMATTED);
+    assert_eq!(read(&formatted), FORMATTED);
+}
+
+#[test]
+fn searches_directories() {
+    let dir = TempDir::new("directories");
+    let top = dir.write("a.nut", UNFORMATTED);
+    let nested = dir.write("scripts/deep/b.nut", UNFORMATTED);
+    let other = dir.write("scripts/notes.txt", UNFORMATTED);
+    // Files given explicitly are formatted whatever their extension
+    let explicit = dir.write("c.txt", UNFORMATTED);
+
+    let output = squirrelfmt(&[&dir.0, &explicit]);
+    assert!(output.status.success());
+    assert_eq!(read(&top), FORMATTED);
+    assert_eq!(read(&nested), FORMATTED);
+    assert_eq!(read(&other), UNFORMATTED);
+    assert_eq!(read(&explicit), FORMATTED);
+}
+
+#[test]
+fn stdin() {
+    let output = squirrelfmt_stdin(&["-"], UNFORMATTED);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), FORMATTED);
+}
+
+#[test]
+fn errors() {
+    let dir = TempDir::new("errors");
+    let invalid = dir.write("a.nut", "x = 0080\n");
+    let valid = dir.write("b.nut", UNFORMATTED);
+
+    // The other files are still formatted
+    let output = squirrelfmt(&[&invalid, &valid]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: invalid digit in octal number\n"));
+    assert!(stderr.contains("a.nut:1:7"));
+    assert_eq!(read(&invalid), "x = 0080\n");
+    assert_eq!(read(&valid), FORMATTED);
+
+    let output = squirrelfmt_stdin(&["-"], "local = 1");
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn check() {
+    let dir = TempDir::new("check");
+    let unformatted = dir.write("a.nut", UNFORMATTED);
+    let formatted = dir.write("b.nut", FORMATTED);
+    let nested = dir.write("c/d.nut", UNFORMATTED);
+
+    let output = squirrelfmt(&[Path::new("--check"), &dir.0]);
+    assert_eq!(output.status.code(), Some(1));
+    // Only the unformatted files are listed, and none of them change
+    let listed = format!("{}\n{}\n", unformatted.display(), nested.display());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), listed);
+    assert_eq!(read(&unformatted), UNFORMATTED);
+    assert_eq!(read(&formatted), FORMATTED);
+    assert_eq!(read(&nested), UNFORMATTED);
+
+    let output = squirrelfmt_stdin(&["--check", "-"], UNFORMATTED);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "<stdin>\n");
+}
+
+#[test]
+fn check_formatted() {
+    let dir = TempDir::new("check-formatted");
+    let formatted = dir.write("a.nut", FORMATTED);
+
+    let output = squirrelfmt(&[Path::new("--check"), &dir.0]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    assert_eq!(read(&formatted), FORMATTED);
+}
+
+#[test]
+fn check_exit_codes() {
+    // Invalid scripts and an invalid command line are told apart from unformatted files
+    let output = squirrelfmt_stdin(&["--check", "-"], "x = 0080");
+    assert_eq!(output.status.code(), Some(3));
+    let output = squirrelfmt_stdin(&["--check", "--dialect", "4.0", "-"], FORMATTED);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn diff() {
+    let dir = TempDir::new("diff");
+    let source = "a()\nx=1\nb()\n";
+    let unformatted = dir.write("a.nut", source);
+    let formatted = dir.write("b.nut", FORMATTED);
+
+    let output = squirrelfmt(&[Path::new("--diff"), &unformatted, &formatted]);
+    assert_eq!(output.status.code(), Some(1));
+    let path = unformatted.display();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("--- {path}\n+++ {path}\n@@ -1,3 +1,3 @@\n a()\n-x=1\n+x = 1\n b()\n")
+    );
+    assert_eq!(read(&unformatted), source);
+
+    let output = squirrelfmt(&[Path::new("--diff"), &formatted]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
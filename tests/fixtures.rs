@@ -0,0 +1,29 @@
+//! Formats every `tests/fixtures/*/input.nut` and compares the result with the `expected.nut` next
+//! to it. Formatting the result again must not change it.
+
+use std::fs;
+use std::path::Path;
+
+use squirrelfmt::config::Config;
+use squirrelfmt::verify::check_idempotent;
+
+#[test]
+fn fixtures() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut failures = Vec::new();
+
+    for entry in fs::read_dir(fixtures).unwrap() {
+        let dir = entry.unwrap().path();
+        let input = fs::read_to_string(dir.join("input.nut")).unwrap();
+        let expected = fs::read_to_string(dir.join("expected.nut")).unwrap();
+
+        let output = squirrelfmt::format_str(&input, &Config::default()).unwrap();
+        if output != expected {
+            failures.push(format!("{}:\n{output}", dir.display()));
+        } else if let Err(error) = check_idempotent(&output, &Config::default()) {
+            failures.push(format!("{}: {error}", dir.display()));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,67 @@
+// A sample script
+local Vector = class {
+    x = 0;
+    y = 0
+    constructor(x, y) {
+        this.x = x;
+        this.y = y
+    }
+    function _add(other) {
+        return ::Vector(x + other.x, y + other.y)
+    }
+    static ZERO = null
+}
+
+function Vector::length() {
+    return sqrt(x * x + y * y)
+}
+
+enum Mode { A, B = 2, C = -3 }
+const MAX = 100
+
+local t = {
+    a = 1,
+    ["b"] = 2,
+    "c": [1, 2, 3],
+    function d(...) {
+        return vargv
+    }
+}
+
+foreach (i, v in t) {
+    if (typeof v == "integer" && v > 1)
+        print(v + "\n")
+    else if (v instanceof Vector) {
+        print("vec")
+    } else
+        print("other")
+}
+
+local gen = function() {
+    for (local i = 0; i < 10; i += 1)
+        yield i;
+}
+local co = ::newthread(gen)
+local fn = @(a, b = 2) a * b
+switch (x) {
+    case 1:
+        a <- 5
+        break
+    default:
+        throw "bad"
+}
+try {
+    local x = a ? b : c
+    x++
+} catch (e) {
+    ::print(e)
+}
+do {
+    i--
+} while (i > 0)
+while (true) {
+    break
+}
+local s = @"verbatim
+string"
+delete t.a
@@ -0,0 +1,55 @@
+// header
+
+local a = 1 // trailing a
+# hash
+
+/* block */ local b = 2
+function foo(
+    aaaaaaaaaaaa,
+    bbbbbbbbbbbbbbbb,
+    cccccccccccccccccc,
+    dddddddddddddddd,
+    eeeeeeeeeeeeeeeeeeeeeee
+) {
+    // inside
+    return aaaaaaaaaaaa +
+        bbbbbbbbbbbbbbbb +
+        cccccccccccccccccc +
+        dddddddddddddddd +
+        eeeeeeeeeeeeeeeeeeeeeee +
+        1
+
+    // before close
+}
+someFunction(argumentNumberOne, argumentNumberTwo, argumentNumberThree, argumentNumberFour, five)
+register("event", function(a, b) {
+    print(a)
+})
+if (
+    someCondition && anotherCondition ||
+    yetAnotherCondition && theFinalCondition && oneMoreCondition
+) {}
+x = [
+    1
+    2
+    ++y
+]
+t <- { a = 1, b = { c = 2, d = [1, 2, 3] }, e = "a long string to make this wrap around", f = 3 }
+foo(
+    a, // why a
+    b
+)
+foo(
+    // leading
+    a
+)
+local v = - -1, w = - --i
+n = 1 .tofloat()
+class A extends B </ attr = 1 /> {
+    </ x = 1, y = "a" />
+    function f() {}
+}
+z = a ?
+    bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb :
+    ccccccccccccccccccccccccccccccccc ? ddddddddddddddddddddd : eeeeeee
+// end